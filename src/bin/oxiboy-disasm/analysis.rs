@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use oxiboy::gameboy::hardware::bus::MemoryBus;
+use oxiboy::gameboy::hardware::cartridge::Cartridge;
+use oxiboy::gameboy::hardware::cpu::disassembler;
+use oxiboy::gameboy::hardware::cpu::instructions::{Dst, Instruction, Src};
+use oxiboy::gameboy::hardware::cpu::registers::Reg8;
+use oxiboy::gameboy::hardware::error::Result;
+
+/// Where execution starts on its own: the boot ROM hands over at $0100 and
+/// the five interrupt vectors can be entered at any time
+const ENTRY_POINTS: [(u16, &str); 6] = [
+    (0x0100, "Start"),
+    (0x0040, "VBlankInterrupt"),
+    (0x0048, "LCDInterrupt"),
+    (0x0050, "TimerInterrupt"),
+    (0x0058, "SerialInterrupt"),
+    (0x0060, "JoypadInterrupt"),
+];
+
+/// Writes to this range select the ROM bank mapped at $4000
+const BANK_SELECT: ::std::ops::RangeInclusive<u16> = 0x2000..=0x3FFF;
+
+/// The CPU's view of the ROM with a given bank mapped at $4000. Anything
+/// outside the ROM reads as open bus.
+pub struct BankView<'a> {
+    fixed: &'a [u8],
+    switchable: &'a [u8],
+}
+
+impl<'a> BankView<'a> {
+    pub fn new(cartridge: &'a Cartridge, bank: usize) -> Self {
+        let switchable = if bank < cartridge.rom_banks() { cartridge.rom_bank(bank) } else { &[] };
+
+        Self {
+            fixed: cartridge.rom_bank(0),
+            switchable,
+        }
+    }
+}
+
+impl<'a> MemoryBus for BankView<'a> {
+    fn read(&self, addr: u16) -> Result<u8> {
+        let byte = match addr {
+            0x0000..=0x3FFF => self.fixed.get(addr as usize),
+            0x4000..=0x7FFF => self.switchable.get(addr as usize - 0x4000),
+            _ => None,
+        };
+
+        Ok(byte.cloned().unwrap_or(0xFF))
+    }
+
+    /// Bank switches are followed by `Analysis` itself, so writes, which
+    /// the disassembler never makes anyway, go nowhere
+    fn write(&mut self, _: u16, _: u8) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The address window a bank is mapped at
+pub fn base(bank: usize) -> u16 {
+    if bank == 0 { 0x0000 } else { 0x4000 }
+}
+
+/// A location to trace from, along with the bank believed to be mapped at
+/// $4000 when execution gets there
+#[derive(Copy, Clone)]
+struct Target {
+    bank: usize,
+    addr: u16,
+    selected: Option<usize>,
+}
+
+/// Code/data map of a ROM, found by following control flow from the entry
+/// points
+pub struct Analysis {
+    /// Per bank, the length of the instruction starting at each offset
+    starts: Vec<Vec<u8>>,
+    /// Per bank, whether each byte belongs to an instruction
+    code: Vec<Vec<bool>>,
+    labels: BTreeMap<(usize, u16), String>,
+    /// Bank of the branch target for each traced jump or call, keyed by the
+    /// bank and address of the instruction. `None` for bank 0 code that
+    /// reaches different banks depending on which one is mapped.
+    branches: HashMap<(usize, u16), Option<usize>>,
+    /// Bank 0 instructions traced so far, with the bank that was believed
+    /// to be mapped at $4000 at the time
+    traced: HashSet<(u16, Option<usize>)>,
+}
+
+impl Analysis {
+    pub fn run(cartridge: &Cartridge) -> Self {
+        let banks = cartridge.rom_banks();
+        let mut analysis = Self {
+            starts: (0..banks).map(|bank| vec![0; cartridge.rom_bank(bank).len()]).collect(),
+            code: (0..banks).map(|bank| vec![false; cartridge.rom_bank(bank).len()]).collect(),
+            labels: BTreeMap::new(),
+            branches: HashMap::new(),
+            traced: HashSet::new(),
+        };
+
+        // bank 1 is mapped at power on
+        let mut pending: Vec<Target> = ENTRY_POINTS
+            .iter()
+            .rev()
+            .map(|&(addr, name)| {
+                analysis.labels.insert((0, addr), name.to_string());
+                Target { bank: 0, addr, selected: Some(1) }
+            })
+            .collect();
+
+        while let Some(target) = pending.pop() {
+            analysis.trace(cartridge, target, &mut pending);
+        }
+
+        analysis.drop_unplaceable_labels();
+        analysis
+    }
+
+    pub fn banks(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn bank_len(&self, bank: usize) -> usize {
+        self.starts[bank].len()
+    }
+
+    /// Length of the instruction starting at `addr`, if code starts there
+    pub fn instruction_at(&self, bank: usize, addr: u16) -> Option<usize> {
+        match self.starts[bank][(addr - base(bank)) as usize] {
+            0 => None,
+            len => Some(len as usize),
+        }
+    }
+
+    pub fn is_code(&self, bank: usize, addr: u16) -> bool {
+        self.code[bank][(addr - base(bank)) as usize]
+    }
+
+    pub fn label(&self, bank: usize, addr: u16) -> Option<&String> {
+        self.labels.get(&(bank, addr))
+    }
+
+    /// Label for the target of the branch at `addr`, if it was traced
+    pub fn branch_label(&self, bank: usize, addr: u16, target: u16) -> Option<String> {
+        let target_bank = match target {
+            0x0000..=0x3FFF => 0,
+            _ => (*self.branches.get(&(bank, addr))?)?,
+        };
+
+        self.labels.get(&(target_bank, target)).cloned()
+    }
+
+    fn trace(&mut self, cartridge: &Cartridge, target: Target, pending: &mut Vec<Target>) {
+        let Target { bank, mut addr, mut selected } = target;
+        let view = BankView::new(cartridge, if bank == 0 { selected.unwrap_or(1) } else { bank });
+        let end = base(bank) as usize + self.bank_len(bank);
+
+        // the value last loaded into A, for following bank switches
+        let mut a = None;
+
+        while (addr as usize) < end && self.first_visit(bank, addr, selected) {
+            let (instr, len) = disassembler::disassemble(&view, addr);
+            if addr as usize + len > end {
+                break;
+            }
+
+            let offset = (addr - base(bank)) as usize;
+            self.starts[bank][offset] = len as u8;
+            for byte in &mut self.code[bank][offset..offset + len] {
+                *byte = true;
+            }
+
+            let next = addr.wrapping_add(len as u16);
+            let mut branch = |dst: u16, kind: &str, selected: Option<usize>| {
+                // code copied to RAM can't be followed from here
+                let dst_bank = match dst {
+                    0x0000..=0x3FFF => 0,
+                    0x4000..=0x7FFF if bank != 0 => bank,
+                    0x4000..=0x7FFF => match selected {
+                        Some(selected) if selected < self.starts.len() => selected,
+                        // nor can a bank that isn't known, and it may not be
+                        // the one another trace of this code saw
+                        _ => {
+                            self.branches.insert((bank, addr), None);
+                            return;
+                        }
+                    },
+                    _ => return,
+                };
+
+                // past the end of a short image or truncated last bank
+                // there's nothing to trace or label
+                if (dst - base(dst_bank)) as usize >= self.starts[dst_bank].len() {
+                    self.branches.insert((bank, addr), None);
+                    return;
+                }
+
+                let traced = self.branches.entry((bank, addr)).or_insert(Some(dst_bank));
+                if *traced != Some(dst_bank) {
+                    *traced = None;
+                }
+                self.labels
+                    .entry((dst_bank, dst))
+                    .or_insert_with(|| format!("{}_{:03X}_{:04X}", kind, dst_bank, dst));
+                pending.push(Target { bank: dst_bank, addr: dst, selected });
+            };
+
+            let fallthrough = match instr {
+                Instruction::Jump(_, offset) => {
+                    branch(next.wrapping_add(offset as u16), "Jump", selected);
+                    false
+                }
+                Instruction::JumpOn(_, _, offset) => {
+                    branch(next.wrapping_add(offset as u16), "Jump", selected);
+                    true
+                }
+                Instruction::JumpAbs(_, dst) => {
+                    branch(dst, "Jump", selected);
+                    false
+                }
+                Instruction::JumpAbsOn(_, _, dst) => {
+                    branch(dst, "Jump", selected);
+                    true
+                }
+                Instruction::Call(_, dst) | Instruction::CallOn(_, _, dst) => {
+                    branch(dst, "Call", selected);
+                    true
+                }
+                Instruction::Restart(_, vector) => {
+                    branch(vector as u16, "Rst", selected);
+                    true
+                }
+                Instruction::Ret(_) | Instruction::RetInterrupt(_) | Instruction::JumpHL(_) => false,
+                Instruction::Lock(_) => false,
+                _ => true,
+            };
+
+            match instr {
+                Instruction::Load(_, Dst::Reg8(Reg8::A), Src::D8(val)) => a = Some(val),
+                Instruction::Load(_, Dst::Addr(dst), Src::Reg8(Reg8::A)) if BANK_SELECT.contains(&dst) => {
+                    // MBCs map bank 0 writes to bank 1
+                    selected = a.map(|bank| (bank as usize).max(1));
+                }
+                Instruction::Load(_, Dst::Reg8(Reg8::A), _) => a = None,
+                Instruction::Load(..) => {}
+                _ => a = None,
+            }
+
+            if !fallthrough {
+                break;
+            }
+
+            addr = next;
+        }
+    }
+
+    /// Whether `addr` still needs tracing. Bank 0 code is traced again for
+    /// every bank it's reached with, as its branches to $4000-$7FFF lead
+    /// into whichever bank is mapped.
+    fn first_visit(&mut self, bank: usize, addr: u16, selected: Option<usize>) -> bool {
+        if bank == 0 {
+            self.traced.insert((addr, selected))
+        } else {
+            self.instruction_at(bank, addr).is_none()
+        }
+    }
+
+    /// A label can only be emitted at the start of an instruction or within
+    /// data; branches into the middle of an instruction keep their address
+    fn drop_unplaceable_labels(&mut self) {
+        let starts = &self.starts;
+        let code = &self.code;
+
+        self.labels.retain(|&(bank, addr), _| {
+            let offset = (addr - base(bank)) as usize;
+            offset < starts[bank].len() && (starts[bank][offset] != 0 || !code[bank][offset])
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxiboy::gameboy::hardware::cartridge::ROM_BANK_SIZE;
+
+    use super::*;
+
+    fn rom(banks: usize, patches: &[(usize, &[u8])]) -> Cartridge {
+        // RST $38 everywhere, the usual filler of unused ROM
+        let mut rom = vec![0xFF; banks * ROM_BANK_SIZE];
+        // MBC1 without RAM, with the ROM size matching the image
+        rom[0x0147..0x014A].copy_from_slice(&[0x01, (banks / 2).trailing_zeros() as u8, 0x00]);
+        for &(offset, bytes) in patches {
+            rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        // interrupt vectors just return
+        for vector in (0x40..=0x60).step_by(8) {
+            rom[vector] = 0xD9;
+        }
+
+        Cartridge::new(rom.into_boxed_slice()).unwrap()
+    }
+
+    #[test]
+    fn follows_control_flow_from_entry() {
+        let cartridge = rom(
+            2,
+            &[
+                // Start: nop ; jp $0150
+                (0x0100, &[0x00, 0xC3, 0x50, 0x01]),
+                // header bytes that must stay data
+                (0x0104, &[0xCE, 0xED, 0x66, 0x66]),
+                // call $0160 ; jr $0150
+                (0x0150, &[0xCD, 0x60, 0x01, 0x18, 0xFB]),
+                // ret
+                (0x0160, &[0xC9]),
+            ],
+        );
+
+        let analysis = Analysis::run(&cartridge);
+
+        assert_eq!(analysis.instruction_at(0, 0x0100), Some(1));
+        assert_eq!(analysis.instruction_at(0, 0x0101), Some(3));
+        assert!(!analysis.is_code(0, 0x0104));
+        assert!(!analysis.is_code(0, 0x0161));
+        assert!(analysis.is_code(0, 0x0153));
+
+        assert_eq!(analysis.label(0, 0x0100).unwrap(), "Start");
+        assert_eq!(analysis.label(0, 0x0150).unwrap(), "Jump_000_0150");
+        assert_eq!(analysis.label(0, 0x0160).unwrap(), "Call_000_0160");
+        assert_eq!(analysis.label(0, 0x0040).unwrap(), "VBlankInterrupt");
+        assert_eq!(analysis.branch_label(0, 0x0153, 0x0150).unwrap(), "Jump_000_0150");
+    }
+
+    #[test]
+    fn follows_bank_switches() {
+        let cartridge = rom(
+            4,
+            &[
+                // ld a, 2 ; ld [$2000], a ; call $4000 ; jp $4010
+                (0x0100, &[0x3E, 0x02, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0xC3, 0x10, 0x40]),
+                // bank 2: ret
+                (2 * ROM_BANK_SIZE, &[0xC9]),
+                // bank 2: jr $4010
+                (2 * ROM_BANK_SIZE + 0x10, &[0x18, 0xFE]),
+            ],
+        );
+
+        let analysis = Analysis::run(&cartridge);
+
+        assert_eq!(analysis.instruction_at(2, 0x4000), Some(1));
+        assert_eq!(analysis.label(2, 0x4000).unwrap(), "Call_002_4000");
+        assert_eq!(analysis.label(2, 0x4010).unwrap(), "Jump_002_4010");
+        assert!(!analysis.is_code(1, 0x4000));
+        assert_eq!(analysis.branch_label(0, 0x0105, 0x4000).unwrap(), "Call_002_4000");
+    }
+
+    #[test]
+    fn shared_trampoline_is_followed_into_each_bank() {
+        let cartridge = rom(
+            4,
+            &[
+                // ld a, 2 ; ld [$2000], a ; call $0150 ; ld a, 3 ; ld [$2000], a ; call $0150 ; jr @
+                (
+                    0x0100,
+                    &[
+                        0x3E, 0x02, 0xEA, 0x00, 0x20, 0xCD, 0x50, 0x01, 0x3E, 0x03, 0xEA, 0x00, 0x20, 0xCD, 0x50,
+                        0x01, 0x18, 0xFE,
+                    ],
+                ),
+                // jp $4000
+                (0x0150, &[0xC3, 0x00, 0x40]),
+                // bank 2: ret
+                (2 * ROM_BANK_SIZE, &[0xC9]),
+                // bank 3: nop ; ret
+                (3 * ROM_BANK_SIZE, &[0x00, 0xC9]),
+            ],
+        );
+
+        let analysis = Analysis::run(&cartridge);
+
+        assert_eq!(analysis.label(2, 0x4000).unwrap(), "Jump_002_4000");
+        assert_eq!(analysis.label(3, 0x4000).unwrap(), "Jump_003_4000");
+        assert!(analysis.is_code(3, 0x4001));
+        // the jump leads to either, so it can't name one
+        assert_eq!(analysis.branch_label(0, 0x0150, 0x4000), None);
+        assert_eq!(analysis.branch_label(0, 0x0105, 0x0150).unwrap(), "Call_000_0150");
+    }
+
+    #[test]
+    fn targets_past_the_end_are_not_followed() {
+        // a 512 byte image: jp $3000
+        let mut rom = vec![0xFF; 0x200];
+        rom[0x0147..0x014A].copy_from_slice(&[0x00, 0x00, 0x00]);
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x00, 0x30]);
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+
+        let analysis = Analysis::run(&cartridge);
+        assert!(analysis.is_code(0, 0x0101));
+        assert_eq!(analysis.label(0, 0x3000), None);
+        assert_eq!(analysis.branch_label(0, 0x0101, 0x3000), None);
+
+        // bank 1 cut short at $4100: jp $6000
+        let mut rom = vec![0xFF; ROM_BANK_SIZE + 0x100];
+        rom[0x0147..0x014A].copy_from_slice(&[0x01, 0x00, 0x00]);
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x00, 0x60]);
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+
+        let analysis = Analysis::run(&cartridge);
+        assert_eq!(analysis.bank_len(1), 0x100);
+        assert_eq!(analysis.label(1, 0x6000), None);
+        assert_eq!(analysis.branch_label(0, 0x0101, 0x6000), None);
+    }
+
+    #[test]
+    fn unknown_bank_is_not_followed() {
+        let cartridge = rom(
+            4,
+            &[
+                // ld a, [hl] ; ld [$2000], a ; call $4000 ; halt
+                (0x0100, &[0x7E, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0x76, 0x18, 0xFE]),
+            ],
+        );
+
+        let analysis = Analysis::run(&cartridge);
+
+        for bank in 1..4 {
+            assert!(!analysis.is_code(bank, 0x4000));
+        }
+        assert_eq!(analysis.branch_label(0, 0x0104, 0x4000), None);
+        assert!(analysis.is_code(0, 0x0107));
+    }
+}
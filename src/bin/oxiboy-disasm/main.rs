@@ -0,0 +1,60 @@
+//! Offline ROM disassembler. Follows control flow from the entry point and
+//! interrupt vectors to tell code from data, and writes RGBDS source.
+//!
+//! Usage: oxiboy-disasm <rom> [output.asm]
+
+#[macro_use]
+extern crate failure;
+extern crate oxiboy;
+
+use std::env;
+use std::fs::File;
+use std::io::{stdout, BufWriter, Read, Write};
+use std::process;
+
+use failure::Error;
+
+use oxiboy::gameboy::hardware::cartridge::Cartridge;
+
+use analysis::Analysis;
+
+mod analysis;
+mod rgbds;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("oxiboy-disasm: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let rom_file_name = match env::args().nth(1) {
+        Some(name) => name,
+        None => bail!("usage: oxiboy-disasm <rom> [output.asm]"),
+    };
+
+    let mut rom = Vec::new();
+    File::open(&rom_file_name)?.read_to_end(&mut rom)?;
+
+    let cartridge = Cartridge::new(rom.into_boxed_slice())?;
+    let analysis = Analysis::run(&cartridge);
+
+    let mut out: Box<dyn Write> = match env::args().nth(2) {
+        Some(name) => Box::new(BufWriter::new(File::create(name)?)),
+        None => Box::new(BufWriter::new(stdout())),
+    };
+
+    writeln!(out, "; Disassembly of {}", rom_file_name)?;
+    for line in cartridge.header().to_string().lines() {
+        writeln!(out, "; {}", line)?;
+    }
+    if let Err(err) = cartridge.verify() {
+        writeln!(out, "; warning: {}", err)?;
+    }
+    writeln!(out)?;
+    rgbds::write(&mut out, &cartridge, &analysis)?;
+    out.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+use oxiboy::gameboy::hardware::cartridge::Cartridge;
+use oxiboy::gameboy::hardware::cpu::disassembler;
+
+use analysis::{base, Analysis, BankView};
+
+/// Bytes per `db` line
+const DATA_COLUMNS: usize = 16;
+
+/// Writes the whole ROM as RGBDS source, one section per bank
+pub fn write<W: Write>(out: &mut W, cartridge: &Cartridge, analysis: &Analysis) -> io::Result<()> {
+    for bank in 0..analysis.banks() {
+        if bank == 0 {
+            writeln!(out, "SECTION \"ROM Bank $000\", ROM0[$0000]")?;
+        } else {
+            writeln!(out, "\nSECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:X}]", bank, bank)?;
+        }
+
+        write_bank(out, cartridge, analysis, bank)?;
+    }
+
+    Ok(())
+}
+
+fn write_bank<W: Write>(out: &mut W, cartridge: &Cartridge, analysis: &Analysis, bank: usize) -> io::Result<()> {
+    let view = BankView::new(cartridge, bank);
+    let bytes = cartridge.rom_bank(bank);
+    let start = base(bank) as usize;
+    let end = start + analysis.bank_len(bank);
+
+    let mut addr = start;
+    while addr < end {
+        if let Some(label) = analysis.label(bank, addr as u16) {
+            writeln!(out, "\n{}:", label)?;
+        }
+
+        if let Some(len) = analysis.instruction_at(bank, addr as u16) {
+            let instr_addr = addr as u16;
+            let (text, _) = disassembler::render_with(&view, instr_addr, |target| {
+                analysis.branch_label(bank, instr_addr, target)
+            });
+
+            // rgbasm always pads STOP with $00, keep any other byte as data
+            if text == "stop" && bytes[addr - start + 1] != 0x00 {
+                writeln!(out, "    db $10, ${:02X}", bytes[addr - start + 1])?;
+            } else {
+                writeln!(out, "    {}", text)?;
+            }
+
+            addr += len;
+            continue;
+        }
+
+        // a data run ends at the next label, instruction or column limit
+        let mut run = vec![bytes[addr - start]];
+        while run.len() < DATA_COLUMNS {
+            let next = addr + run.len();
+            if next >= end || analysis.is_code(bank, next as u16) || analysis.label(bank, next as u16).is_some() {
+                break;
+            }
+
+            run.push(bytes[next - start]);
+        }
+
+        let run: Vec<String> = run.iter().map(|byte| format!("${:02X}", byte)).collect();
+        writeln!(out, "    db {}", run.join(", "))?;
+
+        addr += run.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use oxiboy::gameboy::hardware::cartridge::ROM_BANK_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn emits_code_data_and_labels() {
+        let mut rom = vec![0x00; 2 * ROM_BANK_SIZE];
+        // Start: jp $0150
+        rom[0x0100..0x0103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        // call $0158 ; jr $0150
+        rom[0x0150..0x0155].copy_from_slice(&[0xCD, 0x58, 0x01, 0x18, 0xFB]);
+        // ret
+        rom[0x0158] = 0xC9;
+        for vector in (0x40..=0x60).step_by(8) {
+            rom[vector] = 0xD9;
+        }
+
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        let analysis = Analysis::run(&cartridge);
+
+        let mut out = Vec::new();
+        write(&mut out, &cartridge, &analysis).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        assert!(text.starts_with("SECTION \"ROM Bank $000\", ROM0[$0000]\n    db $00, $00,"));
+        assert!(text.contains("\nVBlankInterrupt:\n    reti\n    db $00,"));
+        assert!(text.contains("\nStart:\n    jp Jump_000_0150\n    db $00,"));
+        assert!(text.contains("\nJump_000_0150:\n    call Call_000_0158\n    jr Jump_000_0150\n    db $00, $00, $00\n"));
+        assert!(text.contains("\nCall_000_0158:\n    ret\n"));
+        assert!(text.contains("\nSECTION \"ROM Bank $001\", ROMX[$4000], BANK[$1]\n    db $00,"));
+    }
+}
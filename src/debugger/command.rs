@@ -6,6 +6,9 @@ pub enum Command {
     Step,
     DumpReg,
     DumpMem,
+    List,
+    Header,
+    Quit,
 }
 
 impl FromStr for Command {
@@ -19,6 +22,9 @@ impl FromStr for Command {
             "c" | "continue" => Ok(Continue),
             "s" | "step" => Ok(Step),
             "r" | "reg" | "registers" => Ok(DumpReg),
+            "l" | "list" => Ok(List),
+            "h" | "header" => Ok(Header),
+            "q" | "quit" => Ok(Quit),
             _ => Err(()),
         }
     }
@@ -1,7 +1,7 @@
 use std::io::{stdin, stdout};
 use std::io::Write;
 
-use super::gameboy::GameBoy;
+use oxiboy::gameboy::GameBoy;
 
 use self::command::Command;
 
@@ -17,6 +17,7 @@ impl Debugger {
         Self { debug: true, gb }
     }
 
+    /// Runs until quit from the prompt
     pub fn run(&mut self) {
         // clear terminal screen and position at top-left
         print!("\x1B[2J\x1B[1;1H");
@@ -26,7 +27,10 @@ impl Debugger {
             [c] continue \
             [bp] add breakpoint \
             [r] dump registers \
-            [m] dump memory\
+            [m] dump memory \
+            [l] list instructions \
+            [h] cartridge header \
+            [q] quit\
         \n");
 
         loop {
@@ -39,27 +43,75 @@ impl Debugger {
                     Ok(Breakpoint) => {
                         let addr = read_stdin().parse::<u16>().unwrap();
 
-                        while self.gb.pc() != addr {
-                            self.gb.step()
-                        }
+                        while self.gb.pc() != addr && self.step() {}
                     }
                     Ok(Continue) => {
                         self.debug = false;
-                        self.gb.step()
+                        self.step();
+                    }
+                    Ok(Step) => {
+                        self.step();
                     }
-                    Ok(Step) => self.gb.step(),
                     Ok(DumpMem) => {
                         let addr = read_stdin().parse::<u16>().unwrap();
-                        println!("${:#04X}: {:#02X}", addr, self.gb.mem(addr));
+                        match self.gb.mem(addr) {
+                            Ok(val) => println!("${:#04X}: {:#02X}", addr, val),
+                            Err(err) => println!("${:#04X}: {}", addr, err),
+                        }
+                    }
+                    Ok(List) => self.list(10),
+                    Ok(Header) => {
+                        let cartridge = self.gb.cartridge();
+                        println!("{}", cartridge.header());
+                        if let Err(err) = cartridge.verify() {
+                            println!("warning: {}", err);
+                        }
                     }
-                    Ok(DumpReg) => println!("\n{:?}", self.gb.cpu.registers),
+                    Ok(DumpReg) => println!("\n{:?}\ncycles: {}", self.gb.cpu.registers, self.gb.cycles()),
+                    // dropping the GameBoy writes the save file
+                    Ok(Quit) => return,
                     _ => println!("invalid input"),
                 };
             } else {
-                self.gb.step()
+                self.step();
             }
         }
     }
+
+    /// Steps the emulation, dropping back to the prompt on a fault. Returns
+    /// whether the step went through.
+    fn step(&mut self) -> bool {
+        match self.gb.step() {
+            Ok(_) => true,
+            Err(err) => {
+                println!("fault at ${:04X}: {}", self.gb.pc(), err);
+                self.debug = true;
+                false
+            }
+        }
+    }
+
+    /// Prints `count` instructions starting at PC, with the flags each one
+    /// touches
+    fn list(&self, count: usize) {
+        let mut addr = self.gb.pc();
+
+        for _ in 0..count {
+            let (text, len) = self.gb.disassemble(addr);
+            let bytes: Vec<String> = (0..len as u16)
+                .map(|offset| match self.gb.mem(addr.wrapping_add(offset)) {
+                    Ok(byte) => format!("{:02X}", byte),
+                    Err(_) => "??".into(),
+                })
+                .collect();
+
+            let marker = if addr == self.gb.pc() { ">" } else { " " };
+            let flags = self.gb.flag_effects(addr);
+            println!("{} ${:04X}  {:<9} {}  {}", marker, addr, bytes.join(" "), flags, text);
+
+            addr = addr.wrapping_add(len as u16);
+        }
+    }
 }
 
 fn read_stdin() -> String {
@@ -1,4 +1,4 @@
-use super::gameboy::GameBoy;
+use oxiboy::gameboy::{EmuError, GameBoy};
 
 pub struct Emulator {
     gb: GameBoy,
@@ -9,9 +9,10 @@ impl Emulator {
         Self { gb: gb }
     }
 
-    pub fn run(&mut self) {
+    /// Runs until the emulation faults
+    pub fn run(&mut self) -> Result<(), EmuError> {
         loop {
-            self.gb.step();
+            self.gb.step()?;
         }
     }
 }
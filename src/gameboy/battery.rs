@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::hardware::cartridge::Cartridge;
+
+/// T-cycles without RAM writes before they're flushed, as games tend to
+/// write a save in several bursts
+const IDLE_CYCLES: u64 = 4_194_304;
+
+/// T-cycles a flush is put off at most while RAM keeps being written
+const MAX_PENDING_CYCLES: u64 = 10 * 4_194_304;
+
+/// The save file of a cartridge with a battery. It holds the RAM as is,
+/// followed by the clock trailer on MBC3, which is what other emulators
+/// read and write too.
+pub struct Battery {
+    path: PathBuf,
+    /// Cycles since the first and the last RAM write that isn't saved yet
+    pending: Option<(u64, u64)>,
+}
+
+impl Battery {
+    /// Loads the save file into `cartridge`, if there is one yet
+    pub fn open(path: PathBuf, cartridge: &mut Cartridge) -> io::Result<Self> {
+        match fs::read(&path) {
+            Ok(data) => cartridge.load_save_data(&data),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        Ok(Self { path, pending: None })
+    }
+
+    /// Flushes RAM once writes to it have settled down
+    pub fn step(&mut self, cartridge: &mut Cartridge, cycles: usize) {
+        let cycles = cycles as u64;
+        self.pending = match (self.pending, cartridge.take_ram_written()) {
+            (None, false) => return,
+            (None, true) => Some((cycles, 0)),
+            (Some((first, _)), true) => Some((first + cycles, 0)),
+            (Some((first, last)), false) => Some((first + cycles, last + cycles)),
+        };
+
+        match self.pending {
+            Some((first, last)) if last >= IDLE_CYCLES || first >= MAX_PENDING_CYCLES => {
+                // on failure, try again after the next write rather than
+                // on every step
+                if let Err(err) = self.flush(cartridge) {
+                    warn!("couldn't write {}: {}", self.path.display(), err);
+                    self.pending = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the save file. It's written next to the old one first, so a
+    /// crash halfway through can't lose both.
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, cartridge.save_data())?;
+        fs::rename(&tmp, &self.path)?;
+
+        self.pending = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    /// An MBC1 cartridge with 8 KiB of battery-backed RAM, enabled
+    fn cartridge() -> Cartridge {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0147..0x014A].copy_from_slice(&[0x03, 0x00, 0x02]);
+        let mut cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        cartridge.write(0x0000, 0x0A);
+        cartridge
+    }
+
+    fn save_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("oxiboy-{}-{}.sav", name, process::id()))
+    }
+
+    #[test]
+    fn loads_an_existing_save() {
+        let path = save_path("load");
+        let mut data = vec![0x00; 0x2000];
+        data[0x1234] = 0x42;
+        fs::write(&path, &data).unwrap();
+
+        let mut cartridge = cartridge();
+        Battery::open(path.clone(), &mut cartridge).unwrap();
+        assert_eq!(cartridge.read_ram(0xB234), 0x42);
+
+        fs::remove_file(&path).unwrap();
+        assert!(Battery::open(path, &mut cartridge).is_ok());
+    }
+
+    #[test]
+    fn flushes_once_writes_settle() {
+        let path = save_path("idle");
+        let mut cartridge = cartridge();
+        let mut battery = Battery::open(path.clone(), &mut cartridge).unwrap();
+
+        cartridge.write_ram(0xA000, 0x12);
+        battery.step(&mut cartridge, 4);
+        battery.step(&mut cartridge, IDLE_CYCLES as usize - 8);
+        assert!(!path.exists());
+
+        // another write puts it off again
+        cartridge.write_ram(0xA001, 0x34);
+        battery.step(&mut cartridge, 4);
+        battery.step(&mut cartridge, IDLE_CYCLES as usize - 8);
+        assert!(!path.exists());
+
+        battery.step(&mut cartridge, 8);
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 0x2000);
+        assert_eq!(&data[..2], &[0x12, 0x34]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignored_writes_are_not_saved() {
+        let path = save_path("ignored");
+        let mut cartridge = cartridge();
+        let mut battery = Battery::open(path.clone(), &mut cartridge).unwrap();
+
+        // with RAM disabled the write goes nowhere
+        cartridge.write(0x0000, 0x00);
+        cartridge.write_ram(0xA000, 0x12);
+        battery.step(&mut cartridge, 4);
+        battery.step(&mut cartridge, IDLE_CYCLES as usize);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn constant_writes_still_get_flushed() {
+        let path = save_path("busy");
+        let mut cartridge = cartridge();
+        let mut battery = Battery::open(path.clone(), &mut cartridge).unwrap();
+
+        let mut cycles = 0;
+        while !path.exists() && cycles <= MAX_PENDING_CYCLES {
+            cartridge.write_ram(0xA000, 0x56);
+            battery.step(&mut cartridge, 70224);
+            cycles += 70224;
+        }
+        assert!(path.exists());
+        assert_eq!(fs::read(&path).unwrap()[0], 0x56);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+pub const NR10_ADDR: u16 = 0xFF10;
+pub const NR52_ADDR: u16 = 0xFF26;
+pub const WAVE_ADDR: u16 = 0xFF30;
+pub const WAVE_END: u16 = 0xFF3F;
+
+/// The frame sequencer runs at 512 Hz
+const FRAME_STEP_CYCLES: usize = 8192;
+
+/// Bits of $FF10-$FF25 that always read back set: write-only fields and
+/// unused bits
+const READ_MASKS: [u8; 0x16] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, // NR50-NR51
+];
+
+/// The sound registers and wave RAM. Nothing is synthesized yet, but the
+/// frame sequencer runs, so channels switch themselves off when their
+/// length runs out and NR52 reports it like the real thing.
+pub struct Apu {
+    regs: [u8; 0x16],
+    wave: [u8; 0x10],
+    powered: bool,
+    enabled: [bool; 4],
+    lengths: [u16; 4],
+    cycles: usize,
+    frame_step: u8,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Self {
+            regs: [0; 0x16],
+            wave: [0; 0x10],
+            powered: false,
+            enabled: [false; 4],
+            lengths: [0; 4],
+            cycles: 0,
+            frame_step: 0,
+        }
+    }
+
+    /// Advances the frame sequencer by `cycles` at the normal clock rate
+    pub fn step(&mut self, cycles: usize) {
+        if !self.powered {
+            return;
+        }
+
+        self.cycles += cycles;
+        while self.cycles >= FRAME_STEP_CYCLES {
+            self.cycles -= FRAME_STEP_CYCLES;
+
+            // lengths are clocked on every other step
+            if self.frame_step & 1 == 0 {
+                self.clock_lengths();
+            }
+            self.frame_step = (self.frame_step + 1) % 8;
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            NR10_ADDR..=0xFF25 => {
+                let reg = (addr - NR10_ADDR) as usize;
+                self.regs[reg] | READ_MASKS[reg]
+            }
+            NR52_ADDR => {
+                let power = if self.powered { 0x80 } else { 0x00 };
+                let status = (0..4)
+                    .filter(|&channel| self.enabled[channel])
+                    .fold(0, |status, channel| status | 1 << channel);
+                0x70 | power | status
+            }
+            0xFF27..=0xFF2F => 0xFF,
+            WAVE_ADDR..=WAVE_END => self.wave[(addr - WAVE_ADDR) as usize],
+            _ => unreachable!("APU read ${:04X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            NR52_ADDR => self.set_power(val & 0x80 != 0),
+            // while powered off only NR52 and wave RAM take writes
+            NR10_ADDR..=0xFF25 if self.powered => self.write_register(addr, val),
+            NR10_ADDR..=0xFF25 | 0xFF27..=0xFF2F => {}
+            WAVE_ADDR..=WAVE_END => self.wave[(addr - WAVE_ADDR) as usize] = val,
+            _ => unreachable!("APU write ${:04X}", addr),
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        let reg = (addr - NR10_ADDR) as usize;
+        self.regs[reg] = val;
+
+        // each channel takes five registers, NR1x to NR4x
+        let channel = reg / 5;
+        if channel > 3 {
+            return;
+        }
+
+        if reg == Self::dac_reg(channel) && !self.dac_on(channel) {
+            self.enabled[channel] = false;
+        }
+
+        match reg % 5 {
+            1 => self.lengths[channel] = Self::max_length(channel) - u16::from(val & Self::length_mask(channel)),
+            4 if val & 0x80 != 0 => self.trigger(channel),
+            _ => {}
+        }
+    }
+
+    fn trigger(&mut self, channel: usize) {
+        if self.lengths[channel] == 0 {
+            self.lengths[channel] = Self::max_length(channel);
+        }
+
+        self.enabled[channel] = self.dac_on(channel);
+    }
+
+    fn set_power(&mut self, on: bool) {
+        if on && !self.powered {
+            self.frame_step = 0;
+            self.cycles = 0;
+        } else if !on {
+            self.regs = [0; 0x16];
+            self.enabled = [false; 4];
+        }
+
+        self.powered = on;
+    }
+
+    fn clock_lengths(&mut self) {
+        for channel in 0..4 {
+            let length_enabled = self.regs[channel * 5 + 4] & 0x40 != 0;
+            if !length_enabled || self.lengths[channel] == 0 {
+                continue;
+            }
+
+            self.lengths[channel] -= 1;
+            if self.lengths[channel] == 0 {
+                self.enabled[channel] = false;
+            }
+        }
+    }
+
+    /// Whether the channel's DAC is on; a channel can't play without it
+    fn dac_on(&self, channel: usize) -> bool {
+        let mask = if channel == 2 { 0x80 } else { 0xF8 };
+        self.regs[Self::dac_reg(channel)] & mask != 0
+    }
+
+    /// NR30 for the wave channel, the volume envelope for the others
+    fn dac_reg(channel: usize) -> usize {
+        if channel == 2 { 0x0A } else { channel * 5 + 2 }
+    }
+
+    fn max_length(channel: usize) -> u16 {
+        if channel == 2 { 256 } else { 64 }
+    }
+
+    fn length_mask(channel: usize) -> u8 {
+        if channel == 2 { 0xFF } else { 0x3F }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered() -> Apu {
+        let mut apu = Apu::new();
+        apu.write(NR52_ADDR, 0x80);
+        apu
+    }
+
+    #[test]
+    fn registers_read_back_through_masks() {
+        let mut apu = powered();
+        apu.write(0xFF11, 0x12);
+        assert_eq!(apu.read(0xFF11), 0x3F);
+        apu.write(0xFF12, 0x34);
+        assert_eq!(apu.read(0xFF12), 0x34);
+        assert_eq!(apu.read(0xFF15), 0xFF);
+        assert_eq!(apu.read(0xFF27), 0xFF);
+        assert_eq!(apu.read(NR52_ADDR), 0xF0);
+    }
+
+    #[test]
+    fn powering_off_clears_and_locks_registers() {
+        let mut apu = powered();
+        apu.write(0xFF24, 0x77);
+        apu.write(WAVE_ADDR, 0x12);
+
+        apu.write(NR52_ADDR, 0x00);
+        assert_eq!(apu.read(0xFF24), 0x00);
+        assert_eq!(apu.read(NR52_ADDR), 0x70);
+
+        apu.write(0xFF24, 0x77);
+        assert_eq!(apu.read(0xFF24), 0x00);
+
+        // wave RAM is left alone either way
+        apu.write(WAVE_END, 0x34);
+        assert_eq!(apu.read(WAVE_ADDR), 0x12);
+        assert_eq!(apu.read(WAVE_END), 0x34);
+    }
+
+    #[test]
+    fn channel_stops_when_its_length_runs_out() {
+        let mut apu = powered();
+        // DAC on, length 2, triggered with the length enabled
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF11, 0x3E);
+        apu.write(0xFF14, 0xC0);
+        assert_eq!(apu.read(NR52_ADDR), 0xF1);
+
+        apu.step(FRAME_STEP_CYCLES);
+        assert_eq!(apu.read(NR52_ADDR), 0xF1);
+        // step 1 doesn't clock lengths, step 2 does
+        apu.step(2 * FRAME_STEP_CYCLES);
+        assert_eq!(apu.read(NR52_ADDR), 0xF0);
+    }
+
+    #[test]
+    fn channel_without_dac_stays_off() {
+        let mut apu = powered();
+        apu.write(0xFF1A, 0x00);
+        apu.write(0xFF1E, 0x80);
+        assert_eq!(apu.read(NR52_ADDR), 0xF0);
+
+        apu.write(0xFF1A, 0x80);
+        apu.write(0xFF1E, 0x80);
+        assert_eq!(apu.read(NR52_ADDR), 0xF4);
+
+        apu.write(0xFF1A, 0x00);
+        assert_eq!(apu.read(NR52_ADDR), 0xF0);
+    }
+}
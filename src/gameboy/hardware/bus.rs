@@ -1,6 +1,15 @@
-use failure::Error;
+use super::error::Result;
 
 pub trait MemoryBus {
-    fn read(&self, addr: u16) -> Result<u8, Error>;
-    fn write(&mut self, addr: u16, val: u8) -> Result<(), Error>;
+    fn read(&self, addr: u16) -> Result<u8>;
+    fn write(&mut self, addr: u16, val: u8) -> Result<()>;
+
+    /// Advances the rest of the hardware by the given number of T-cycles
+    fn tick(&mut self, _cycles: usize) {}
+
+    /// Performs the CGB speed switch when STOP executes with one armed in
+    /// KEY1, returning whether it happened
+    fn speed_switch(&mut self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,316 @@
+use super::frames::Frames;
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+
+/// Size of the picture the sensor hands over, in pixels
+const WIDTH: usize = 128;
+const HEIGHT: usize = 112;
+
+/// Where a capture lands in RAM bank 0: 16x14 tiles in the PPU's format
+const IMAGE_ADDR: usize = 0x0100;
+
+/// Registers: control, parameters, exposure time and the 4x4 matrix of
+/// three thresholds each that turns the picture into 4 shades
+const REGISTERS: usize = 0x36;
+const MATRIX: usize = 0x06;
+
+const CAPTURE: u8 = 0x01;
+
+/// How much the edge enhancement ratio in bits 4-6 of register 4 adds
+const EDGE_RATIOS: [i32; 8] = [2, 3, 4, 5, 8, 12, 16, 20];
+
+/// The Pocket Camera, or Game Boy Camera: up to 1 MiB of ROM, 128 KiB of
+/// RAM and an M64282FP image sensor. RAM bank $10 maps the sensor's
+/// registers instead of RAM.
+///
+/// The sensor looks at `Frames` from the host. Its analog side is only
+/// approximated: exposure scales brightness, and the 2D edge mode sharpens
+/// by the configured ratio. Dithering and the tile layout in RAM are what
+/// the real camera does, so the game shows and saves pictures as usual.
+pub struct Camera {
+    ram_writable: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    regs: [u8; REGISTERS],
+    /// T-cycles left of the capture in progress
+    capturing: usize,
+    frames: Option<Frames>,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            ram_writable: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            regs: [0x00; REGISTERS],
+            capturing: 0,
+            frames: None,
+        }
+    }
+
+    fn registers_mapped(&self) -> bool {
+        self.ram_bank & 0x10 != 0
+    }
+
+    fn exposure(&self) -> usize {
+        (self.regs[2] as usize) << 8 | self.regs[3] as usize
+    }
+
+    /// How long a capture takes: a fixed readout time, a bit more without
+    /// the N bit, and the exposure time in units of 16 CPU cycles
+    fn capture_cycles(&self) -> usize {
+        let n = if self.regs[1] & 0x80 != 0 { 0 } else { 512 };
+        (32446 + n + 16 * self.exposure()) * 4
+    }
+
+    /// The scene as the sensor sees it: 0 for black to 255 for white
+    fn expose(&mut self) -> Vec<i32> {
+        let exposure = self.exposure() as i32;
+        let mut image = vec![0; WIDTH * HEIGHT];
+
+        if let Some(frame) = self.frames.as_mut().and_then(|frames| frames.next_frame()) {
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let light = frame.sample(x, y, WIDTH, HEIGHT) as i32;
+                    // $0800 is about right for a well lit scene
+                    image[y * WIDTH + x] = (light * exposure / 0x0800).min(255);
+                }
+            }
+        }
+
+        // 2D edge enhancement: each pixel minus a share of its neighbours
+        if self.regs[1] & 0x60 == 0x60 {
+            let ratio = EDGE_RATIOS[(self.regs[4] >> 4) as usize & 0x07];
+            let at = |image: &[i32], x: isize, y: isize| {
+                let x = x.clamp(0, WIDTH as isize - 1) as usize;
+                let y = y.clamp(0, HEIGHT as isize - 1) as usize;
+                image[y * WIDTH + x]
+            };
+
+            let source = image.clone();
+            for y in 0..HEIGHT as isize {
+                for x in 0..WIDTH as isize {
+                    let center = at(&source, x, y);
+                    let edge = 4 * center
+                        - at(&source, x - 1, y)
+                        - at(&source, x + 1, y)
+                        - at(&source, x, y - 1)
+                        - at(&source, x, y + 1);
+                    image[y as usize * WIDTH + x as usize] = (center + edge * ratio / 4).clamp(0, 255);
+                }
+            }
+        }
+
+        if self.regs[4] & 0x08 != 0 {
+            for pixel in &mut image {
+                *pixel = 255 - *pixel;
+            }
+        }
+
+        image
+    }
+
+    /// Dithers a capture to 4 shades and stores it as tiles in RAM, if
+    /// there's room for it
+    fn develop(&mut self, ram: &mut [u8]) -> bool {
+        if ram.len() < IMAGE_ADDR + WIDTH * HEIGHT / 4 {
+            return false;
+        }
+
+        let image = self.expose();
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let matrix = MATRIX + ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.regs[matrix..matrix + 3];
+                let pixel = image[y * WIDTH + x];
+                let shade = match thresholds.iter().position(|&threshold| pixel < threshold as i32) {
+                    Some(i) => 3 - i as u8,
+                    None => 0,
+                };
+
+                let tile = (y / 8) * (WIDTH / 8) + x / 8;
+                let offset = IMAGE_ADDR + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                for (plane, byte) in ram[offset..offset + 2].iter_mut().enumerate() {
+                    if shade >> plane & 0x01 != 0 {
+                        *byte |= bit;
+                    } else {
+                        *byte &= !bit;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl Mbc for Camera {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_writable = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = val & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = val & 0x1F,
+            _ => {}
+        }
+    }
+
+    /// RAM reads don't need enabling. Of the registers only the first can be
+    /// read, telling whether a capture is still going on.
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.registers_mapped() {
+            return read_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr));
+        }
+
+        match addr & 0x7F {
+            0x00 if self.capturing > 0 => self.regs[0] | CAPTURE,
+            0x00 => self.regs[0] & !CAPTURE,
+            _ => 0x00,
+        }
+    }
+
+    /// A capture counts as a RAM write, as the picture lands there
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.registers_mapped() {
+            return self.ram_writable && write_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr), val);
+        }
+
+        let reg = addr as usize & 0x7F;
+        if reg < REGISTERS {
+            self.regs[reg] = val;
+        }
+        // the picture is stored right away rather than when the capture
+        // ends, as games wait for the busy bit before looking at it
+        if reg == 0x00 && val & CAPTURE != 0 && self.capturing == 0 {
+            self.capturing = self.capture_cycles();
+            return self.develop(ram);
+        }
+
+        false
+    }
+
+    fn step(&mut self, cycles: usize) {
+        if self.capturing == 0 {
+            return;
+        }
+
+        self.capturing = self.capturing.saturating_sub(cycles);
+        if self.capturing == 0 {
+            self.regs[0] &= !CAPTURE;
+        }
+    }
+
+    fn set_camera_frames(&mut self, frames: Frames) {
+        self.frames = Some(frames);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::frames::Frame;
+    use super::*;
+
+    fn registers(mbc: &mut Camera, ram: &mut [u8], regs: &[(u16, u8)]) {
+        mbc.write_register(0x4000, 0x10);
+        for &(reg, val) in regs {
+            mbc.write_ram(ram, 0xA000 + reg, val);
+        }
+    }
+
+    /// A matrix with the same thresholds everywhere
+    fn flat_matrix(thresholds: [u8; 3]) -> Vec<(u16, u8)> {
+        (0..48).map(|i| (MATRIX as u16 + i, thresholds[i as usize % 3])).collect()
+    }
+
+    /// The shade of a pixel in the captured tiles
+    fn shade(ram: &[u8], x: usize, y: usize) -> u8 {
+        let offset = IMAGE_ADDR + ((y / 8) * 16 + x / 8) * 16 + (y % 8) * 2;
+        let bit = 7 - x % 8;
+        (ram[offset] >> bit & 0x01) | (ram[offset + 1] >> bit & 0x01) << 1
+    }
+
+    #[test]
+    fn ram_is_always_readable() {
+        let mut mbc = Camera::new();
+        let mut ram = vec![0x00; 0x20000];
+
+        mbc.write_register(0x4000, 0x0F);
+        assert!(!mbc.write_ram(&mut ram, 0xA000, 0x42));
+        assert_eq!(ram[0x1E000], 0x00);
+
+        mbc.write_register(0x0000, 0x0A);
+        assert!(mbc.write_ram(&mut ram, 0xA000, 0x42));
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x42);
+    }
+
+    #[test]
+    fn capture_stays_busy_for_the_exposure_time() {
+        let mut mbc = Camera::new();
+        let mut ram = vec![0x00; 0x20000];
+        registers(&mut mbc, &mut ram, &[(0x01, 0x80), (0x02, 0x00), (0x03, 0x10), (0x00, 0x03)]);
+
+        let cycles = (32446 + 16 * 0x10) * 4;
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x03);
+        mbc.step(cycles - 1);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x03);
+        mbc.step(1);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x02);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x00);
+    }
+
+    #[test]
+    fn develops_a_dithered_picture_into_ram() {
+        // dark on the left, white on the right
+        let mut pixels = vec![0x00; 4 * 2];
+        pixels[2] = 0xFF;
+        pixels[3] = 0xFF;
+        pixels[6] = 0xFF;
+        pixels[7] = 0xFF;
+        let mut mbc = Camera::new();
+        mbc.set_camera_frames(Frames::new(vec![Frame::new(4, 2, pixels)]));
+
+        let mut ram = vec![0x00; 0x20000];
+        let mut regs = flat_matrix([0x40, 0x80, 0xC0]);
+        regs.extend_from_slice(&[(0x01, 0x80), (0x02, 0x08), (0x03, 0x00), (0x00, 0x01)]);
+        registers(&mut mbc, &mut ram, &regs);
+
+        assert_eq!(shade(&ram, 0, 0), 3);
+        assert_eq!(shade(&ram, 63, 111), 3);
+        assert_eq!(shade(&ram, 64, 0), 0);
+        assert_eq!(shade(&ram, 127, 111), 0);
+        assert_eq!(&ram[IMAGE_ADDR + 8 * 16..IMAGE_ADDR + 8 * 16 + 2], &[0x00, 0x00]);
+
+        // at half the exposure white comes out dark gray
+        mbc.step(usize::MAX);
+        registers(&mut mbc, &mut ram, &[(0x02, 0x04), (0x00, 0x01)]);
+        assert_eq!(shade(&ram, 127, 0), 2);
+
+        // inverted
+        mbc.step(usize::MAX);
+        registers(&mut mbc, &mut ram, &[(0x02, 0x08), (0x04, 0x08), (0x00, 0x01)]);
+        assert_eq!(shade(&ram, 0, 0), 0);
+        assert_eq!(shade(&ram, 127, 0), 3);
+    }
+
+    #[test]
+    fn no_frames_is_a_dark_picture() {
+        let mut mbc = Camera::new();
+        let mut ram = vec![0x00; 0x20000];
+        let mut regs = flat_matrix([0x40, 0x80, 0xC0]);
+        regs.push((0x00, 0x01));
+        registers(&mut mbc, &mut ram, &regs);
+
+        assert!(ram[IMAGE_ADDR..IMAGE_ADDR + 0xE00].iter().all(|&byte| byte == 0xFF));
+    }
+}
@@ -0,0 +1,285 @@
+/// Size of a 93LC56: 128 16-bit words
+pub const SIZE: usize = 0x100;
+
+const CS: u8 = 0x80;
+const CLK: u8 = 0x40;
+const DI: u8 = 0x02;
+const DO: u8 = 0x01;
+
+/// Bits in a command: the start bit, a 2-bit opcode and an 8-bit address
+const COMMAND_BITS: u8 = 11;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// Waiting for a start bit, or shifting in the rest of a command
+    Command,
+    /// Shifting a word out, bit by bit
+    Read,
+    /// Shifting in the word to write to an address
+    Write(usize),
+    /// Shifting in the word to write everywhere
+    WriteAll,
+}
+
+/// A 93LC56 serial EEPROM, as on MBC7 cartridges. The game bit-bangs its
+/// chip select, clock and data lines; the contents live in the cartridge
+/// RAM, stored as little-endian words, so they're saved like any RAM.
+pub struct Eeprom {
+    pins: u8,
+    state: State,
+    shift: u32,
+    bits: u8,
+    write_enabled: bool,
+}
+
+impl Eeprom {
+    pub fn new() -> Self {
+        Self {
+            pins: DO,
+            state: State::Command,
+            shift: 0,
+            bits: 0,
+            write_enabled: false,
+        }
+    }
+
+    /// Chip select in bit 7, clock in bit 6, data in in bit 1 and data out
+    /// in bit 0
+    pub fn read(&self) -> u8 {
+        self.pins
+    }
+
+    /// Returns whether the write finished a command that changed the
+    /// contents
+    pub fn write(&mut self, ram: &mut [u8], val: u8) -> bool {
+        let rising = val & CLK != 0 && self.pins & CLK == 0;
+        self.pins = val & (CS | CLK | DI) | self.pins & DO;
+
+        // dropping chip select aborts whatever was going on
+        if val & CS == 0 {
+            self.state = State::Command;
+            self.shift = 0;
+            self.bits = 0;
+            return false;
+        }
+
+        rising && self.clock(ram, val & DI != 0)
+    }
+
+    fn clock(&mut self, ram: &mut [u8], di: bool) -> bool {
+        if self.state == State::Read {
+            self.set_do(self.shift & 0x8000 != 0);
+            self.shift <<= 1;
+            self.bits -= 1;
+            if self.bits == 0 {
+                self.state = State::Command;
+            }
+            return false;
+        }
+
+        // clocks before the start bit are ignored
+        if self.state == State::Command && self.bits == 0 && !di {
+            return false;
+        }
+
+        self.shift = self.shift << 1 | di as u32;
+        self.bits += 1;
+
+        match self.state {
+            State::Command if self.bits == COMMAND_BITS => self.command(ram),
+            State::Write(addr) if self.bits == 16 => {
+                if self.write_enabled {
+                    write_word(ram, addr, self.shift as u16);
+                }
+                self.finish();
+                self.write_enabled
+            }
+            State::WriteAll if self.bits == 16 => {
+                if self.write_enabled {
+                    for addr in 0..SIZE / 2 {
+                        write_word(ram, addr, self.shift as u16);
+                    }
+                }
+                self.finish();
+                self.write_enabled
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the command erased anything
+    fn command(&mut self, ram: &mut [u8]) -> bool {
+        // the x16 organisation only uses 7 of the address bits, but the top
+        // two tell the variants of opcode 00 apart
+        let opcode = (self.shift >> 8) & 0x03;
+        let addr = self.shift as usize & 0x7F;
+        let variant = (self.shift >> 6) & 0x03;
+        self.shift = 0;
+        self.bits = 0;
+
+        match (opcode, variant) {
+            (0b10, _) => {
+                // a dummy zero comes out ahead of the word
+                self.state = State::Read;
+                self.shift = read_word(ram, addr) as u32;
+                self.bits = 16;
+                self.set_do(false);
+                false
+            }
+            (0b01, _) => {
+                self.state = State::Write(addr);
+                false
+            }
+            (0b11, _) => {
+                if self.write_enabled {
+                    write_word(ram, addr, 0xFFFF);
+                }
+                self.finish();
+                self.write_enabled
+            }
+            (_, 0b00) => {
+                self.write_enabled = false;
+                self.finish();
+                false
+            }
+            (_, 0b01) => {
+                self.state = State::WriteAll;
+                false
+            }
+            (_, 0b10) => {
+                if self.write_enabled {
+                    for addr in 0..SIZE / 2 {
+                        write_word(ram, addr, 0xFFFF);
+                    }
+                }
+                self.finish();
+                self.write_enabled
+            }
+            _ => {
+                self.write_enabled = true;
+                self.finish();
+                false
+            }
+        }
+    }
+
+    /// Writes take no time here, so the chip reports ready right away
+    fn finish(&mut self) {
+        self.state = State::Command;
+        self.shift = 0;
+        self.bits = 0;
+        self.set_do(true);
+    }
+
+    fn set_do(&mut self, high: bool) {
+        self.pins = self.pins & !DO | if high { DO } else { 0 };
+    }
+}
+
+fn read_word(ram: &[u8], addr: usize) -> u16 {
+    match ram.get(addr * 2..addr * 2 + 2) {
+        Some(word) => (word[1] as u16) << 8 | word[0] as u16,
+        None => 0xFFFF,
+    }
+}
+
+fn write_word(ram: &mut [u8], addr: usize, val: u16) {
+    if let Some(word) = ram.get_mut(addr * 2..addr * 2 + 2) {
+        word[0] = val as u8;
+        word[1] = (val >> 8) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clocks `count` bits of `val` in, most significant first, and
+    /// returns what came out on DO
+    fn transfer(eeprom: &mut Eeprom, ram: &mut [u8], val: u32, count: u8) -> u32 {
+        let mut out = 0;
+        for bit in (0..count).rev() {
+            let di = if val >> bit & 1 != 0 { DI } else { 0 };
+            eeprom.write(ram, CS | di);
+            eeprom.write(ram, CS | CLK | di);
+            out = out << 1 | (eeprom.read() & DO) as u32;
+        }
+        out
+    }
+
+    /// A start bit, `opcode` and `addr`
+    fn command(opcode: u32, addr: u32) -> u32 {
+        1 << 10 | opcode << 8 | addr
+    }
+
+    fn deselect(eeprom: &mut Eeprom, ram: &mut [u8]) {
+        eeprom.write(ram, 0x00);
+    }
+
+    #[test]
+    fn reads_words_msb_first() {
+        let mut eeprom = Eeprom::new();
+        let mut ram = vec![0xFF; SIZE];
+        ram[0x0A] = 0x34;
+        ram[0x0B] = 0x12;
+
+        // READ address 5
+        transfer(&mut eeprom, &mut ram, command(0b10, 0x05), COMMAND_BITS);
+        assert_eq!(eeprom.read() & DO, 0);
+        assert_eq!(transfer(&mut eeprom, &mut ram, 0, 16), 0x1234);
+    }
+
+    #[test]
+    fn writes_need_enabling() {
+        let mut eeprom = Eeprom::new();
+        let mut ram = vec![0xFF; SIZE];
+
+        // leading zeros before the start bit don't count
+        transfer(&mut eeprom, &mut ram, command(0b01, 0x01), COMMAND_BITS + 3);
+        transfer(&mut eeprom, &mut ram, 0xBEEF, 16);
+        deselect(&mut eeprom, &mut ram);
+        assert_eq!(&ram[2..4], &[0xFF, 0xFF]);
+
+        // EWEN
+        transfer(&mut eeprom, &mut ram, command(0b00, 0xC0), COMMAND_BITS);
+        deselect(&mut eeprom, &mut ram);
+        transfer(&mut eeprom, &mut ram, command(0b01, 0x01), COMMAND_BITS);
+        transfer(&mut eeprom, &mut ram, 0xBEEF, 16);
+        assert_eq!(eeprom.read() & DO, DO);
+        deselect(&mut eeprom, &mut ram);
+        assert_eq!(&ram[2..4], &[0xEF, 0xBE]);
+
+        // ERASE, reported as a change on its last bit
+        transfer(&mut eeprom, &mut ram, command(0b11, 0x01) >> 1, COMMAND_BITS - 1);
+        eeprom.write(&mut ram, CS);
+        assert!(eeprom.write(&mut ram, CS | CLK | DI));
+        deselect(&mut eeprom, &mut ram);
+        assert_eq!(&ram[2..4], &[0xFF, 0xFF]);
+
+        // WRAL, then EWDS
+        transfer(&mut eeprom, &mut ram, command(0b00, 0x40), COMMAND_BITS);
+        transfer(&mut eeprom, &mut ram, 0x0000, 16);
+        deselect(&mut eeprom, &mut ram);
+        assert!(ram.iter().all(|&byte| byte == 0x00));
+
+        transfer(&mut eeprom, &mut ram, command(0b00, 0x00), COMMAND_BITS);
+        deselect(&mut eeprom, &mut ram);
+        transfer(&mut eeprom, &mut ram, command(0b00, 0x80), COMMAND_BITS);
+        deselect(&mut eeprom, &mut ram);
+        assert!(ram.iter().all(|&byte| byte == 0x00));
+    }
+
+    #[test]
+    fn deselecting_aborts_a_command() {
+        let mut eeprom = Eeprom::new();
+        let mut ram = vec![0x00; SIZE];
+        transfer(&mut eeprom, &mut ram, command(0b00, 0xC0), COMMAND_BITS);
+        deselect(&mut eeprom, &mut ram);
+
+        transfer(&mut eeprom, &mut ram, command(0b01, 0x00), COMMAND_BITS);
+        transfer(&mut eeprom, &mut ram, 0xFF, 8);
+        deselect(&mut eeprom, &mut ram);
+        transfer(&mut eeprom, &mut ram, 0xFF, 8);
+        assert_eq!(&ram[0..2], &[0x00, 0x00]);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A grayscale picture, 0 being black and 255 white
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+
+        Self { width, height, pixels }
+    }
+
+    /// Parses a PGM (portable graymap) file, binary or plain. It's the one
+    /// grayscale format any image tool can write, and simple enough not to
+    /// need a library.
+    pub fn from_pgm(data: &[u8]) -> io::Result<Self> {
+        let mut pos = 0;
+        let mut header = [(0, 0); 4];
+        for field in &mut header {
+            *field = next_token(data, &mut pos).ok_or_else(|| invalid("truncated PGM header"))?;
+        }
+
+        let (magic, width, height, maxval) = (header[0], header[1], header[2], header[3]);
+        let width = number(&data[width.0..width.1])?;
+        let height = number(&data[height.0..height.1])?;
+        let maxval = number(&data[maxval.0..maxval.1])?;
+        if maxval == 0 || maxval > 255 {
+            return Err(invalid("only 8-bit PGM files are supported"));
+        }
+
+        let raw: Vec<usize> = match &data[magic.0..magic.1] {
+            // a single whitespace byte separates the header from the pixels
+            b"P5" => data
+                .get(pos + 1..pos + 1 + width * height)
+                .ok_or_else(|| invalid("truncated PGM data"))?
+                .iter()
+                .map(|&byte| byte as usize)
+                .collect(),
+            b"P2" => {
+                let mut raw = Vec::with_capacity(width * height);
+                while let Some(token) = next_token(data, &mut pos) {
+                    raw.push(number(&data[token.0..token.1])?);
+                }
+                raw
+            }
+            _ => return Err(invalid("not a PGM file")),
+        };
+        if raw.len() < width * height {
+            return Err(invalid("truncated PGM data"));
+        }
+
+        let pixels = raw[..width * height]
+            .iter()
+            .map(|&val| (val.min(maxval) * 255 / maxval) as u8)
+            .collect();
+        Ok(Self::new(width, height, pixels))
+    }
+
+    /// The pixel at (`x`, `y`) of a `width` by `height` view of the frame,
+    /// stretched to fit
+    pub fn sample(&self, x: usize, y: usize, width: usize, height: usize) -> u8 {
+        if self.pixels.is_empty() {
+            return 0;
+        }
+
+        self.pixels[y * self.height / height * self.width + x * self.width / width]
+    }
+}
+
+/// Where the camera's pictures come from: a single image, or a sequence of
+/// them taken in turn, one per capture
+pub struct Frames {
+    frames: Vec<Frame>,
+    next: usize,
+}
+
+impl Frames {
+    pub fn new(frames: Vec<Frame>) -> Self {
+        Self { frames, next: 0 }
+    }
+
+    /// Loads a PGM file, or every PGM file in a directory in name order
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Ok(Self::new(vec![Frame::from_pgm(&fs::read(path)?)?]));
+        }
+
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "pgm") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut frames = Vec::new();
+        for path in paths {
+            frames.push(Frame::from_pgm(&fs::read(path)?)?);
+        }
+        if frames.is_empty() {
+            return Err(invalid("no .pgm files in the directory"));
+        }
+
+        Ok(Self::new(frames))
+    }
+
+    /// The frame to capture, moving on to the next one for next time
+    pub fn next_frame(&mut self) -> Option<&Frame> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        let i = self.next;
+        self.next = (self.next + 1) % self.frames.len();
+        Some(&self.frames[i])
+    }
+}
+
+/// The start and end of the next whitespace separated token, skipping
+/// comments
+fn next_token(data: &[u8], pos: &mut usize) -> Option<(usize, usize)> {
+    loop {
+        match data.get(*pos) {
+            Some(b'#') => {
+                while data.get(*pos).is_some_and(|&byte| byte != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return None,
+        }
+    }
+
+    let start = *pos;
+    while data.get(*pos).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+
+    Some((start, *pos))
+}
+
+fn number(token: &[u8]) -> io::Result<usize> {
+    String::from_utf8_lossy(token)
+        .parse()
+        .map_err(|_| invalid("bad number in PGM file"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_binary_pgm() {
+        let mut data = b"P5\n# a comment\n2 2\n255\n".to_vec();
+        data.extend_from_slice(&[0x00, 0x40, 0x80, 0xFF]);
+
+        let frame = Frame::from_pgm(&data).unwrap();
+        assert_eq!(frame, Frame::new(2, 2, vec![0x00, 0x40, 0x80, 0xFF]));
+    }
+
+    #[test]
+    fn parses_plain_pgm_and_scales_to_8_bits() {
+        let frame = Frame::from_pgm(b"P2 3 1 15\n0 15 5\n").unwrap();
+        assert_eq!(frame, Frame::new(3, 1, vec![0, 255, 85]));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(Frame::from_pgm(b"P6 1 1 255\n\x00\x00\x00").is_err());
+        assert!(Frame::from_pgm(b"P5 2 2 255\n\x00").is_err());
+        assert!(Frame::from_pgm(b"P5 2").is_err());
+    }
+
+    #[test]
+    fn stretches_to_the_sensor() {
+        let frame = Frame::new(2, 1, vec![0x10, 0x20]);
+        assert_eq!(frame.sample(0, 0, 128, 112), 0x10);
+        assert_eq!(frame.sample(63, 111, 128, 112), 0x10);
+        assert_eq!(frame.sample(64, 0, 128, 112), 0x20);
+    }
+
+    #[test]
+    fn takes_frames_in_turn() {
+        let a = Frame::new(1, 1, vec![1]);
+        let b = Frame::new(1, 1, vec![2]);
+        let mut frames = Frames::new(vec![a.clone(), b.clone()]);
+
+        assert_eq!(frames.next_frame(), Some(&a));
+        assert_eq!(frames.next_frame(), Some(&b));
+        assert_eq!(frames.next_frame(), Some(&a));
+        assert_eq!(Frames::new(Vec::new()).next_frame(), None);
+    }
+}
@@ -0,0 +1,426 @@
+use std::error;
+use std::fmt;
+
+pub const LOGO_ADDR: usize = 0x0104;
+const TITLE_ADDR: usize = 0x0134;
+const MANUFACTURER_ADDR: usize = 0x013F;
+const CGB_FLAG_ADDR: usize = 0x0143;
+const NEW_LICENSEE_ADDR: usize = 0x0144;
+const SGB_FLAG_ADDR: usize = 0x0146;
+const CARTRIDGE_TYPE_ADDR: usize = 0x0147;
+const ROM_SIZE_ADDR: usize = 0x0148;
+const RAM_SIZE_ADDR: usize = 0x0149;
+const DESTINATION_ADDR: usize = 0x014A;
+const OLD_LICENSEE_ADDR: usize = 0x014B;
+const VERSION_ADDR: usize = 0x014C;
+const HEADER_CHECKSUM_ADDR: usize = 0x014D;
+const GLOBAL_CHECKSUM_ADDR: usize = 0x014E;
+
+/// The header ends with the global checksum at $014F
+const HEADER_END: usize = 0x0150;
+
+/// The boot ROM refuses to start a cartridge without this bitmap
+pub const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// Old licensee code meaning the new one at $0144 applies
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+/// Why a ROM image can't be used, or doesn't check out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The image ends before the header does
+    TooShort { len: usize },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    /// The boot ROM refuses to start a cartridge failing this one
+    HeaderChecksum { expected: u8, actual: u8 },
+    /// Not checked by the hardware, but a sign of a bad dump
+    GlobalChecksum { expected: u16, actual: u16 },
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::HeaderError::*;
+
+        match *self {
+            TooShort { len } => write!(f, "ROM of {} bytes is too short to hold a header", len),
+            UnknownCartridgeType(code) => write!(f, "unknown cartridge type ${:02X}", code),
+            UnknownRomSize(code) => write!(f, "unknown ROM size ${:02X}", code),
+            UnknownRamSize(code) => write!(f, "unknown RAM size ${:02X}", code),
+            HeaderChecksum { expected, actual } => {
+                write!(f, "header checksum is ${:02X}, should be ${:02X}", actual, expected)
+            }
+            GlobalChecksum { expected, actual } => {
+                write!(f, "global checksum is ${:04X}, should be ${:04X}", actual, expected)
+            }
+        }
+    }
+}
+
+impl error::Error for HeaderError {}
+
+/// The chip between the CPU and the ROM and RAM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+}
+
+/// The mapper and whatever else is on the board, from header byte $0147
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Option<Self> {
+        use self::Mapper::*;
+
+        let (mapper, ram, battery, timer, rumble) = match code {
+            0x00 => (RomOnly, false, false, false, false),
+            0x01 => (Mbc1, false, false, false, false),
+            0x02 => (Mbc1, true, false, false, false),
+            0x03 => (Mbc1, true, true, false, false),
+            0x05 => (Mbc2, false, false, false, false),
+            0x06 => (Mbc2, false, true, false, false),
+            0x08 => (RomOnly, true, false, false, false),
+            0x09 => (RomOnly, true, true, false, false),
+            0x0B => (Mmm01, false, false, false, false),
+            0x0C => (Mmm01, true, false, false, false),
+            0x0D => (Mmm01, true, true, false, false),
+            0x0F => (Mbc3, false, true, true, false),
+            0x10 => (Mbc3, true, true, true, false),
+            0x11 => (Mbc3, false, false, false, false),
+            0x12 => (Mbc3, true, false, false, false),
+            0x13 => (Mbc3, true, true, false, false),
+            0x19 => (Mbc5, false, false, false, false),
+            0x1A => (Mbc5, true, false, false, false),
+            0x1B => (Mbc5, true, true, false, false),
+            0x1C => (Mbc5, false, false, false, true),
+            0x1D => (Mbc5, true, false, false, true),
+            0x1E => (Mbc5, true, true, false, true),
+            0x20 => (Mbc6, true, true, false, false),
+            0x22 => (Mbc7, true, true, false, true),
+            0xFC => (PocketCamera, true, true, false, false),
+            0xFD => (Tama5, true, true, true, false),
+            0xFE => (HuC3, true, true, true, false),
+            0xFF => (HuC1, true, true, false, false),
+            _ => return None,
+        };
+
+        Some(Self {
+            code,
+            mapper,
+            ram,
+            battery,
+            timer,
+            rumble,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// A monochrome game, run in compatibility mode on CGB
+    None,
+    /// Uses CGB features but still runs on the monochrome models
+    Enhanced,
+    Only,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Licensee {
+    /// One byte publisher code, used by older games
+    Old(u8),
+    /// Two character code that replaced it
+    New(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+/// The cartridge header at $0100-$014F
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    /// Four letter product code, only on newer games
+    pub manufacturer: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub licensee: Licensee,
+    pub cartridge_type: CartridgeType,
+    /// ROM size in bytes
+    pub rom_size: usize,
+    /// External RAM size in bytes. Zero for MBC2, whose RAM is built in.
+    pub ram_size: usize,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<Self, HeaderError> {
+        if rom.len() < HEADER_END {
+            return Err(HeaderError::TooShort { len: rom.len() });
+        }
+
+        let cgb = match rom[CGB_FLAG_ADDR] {
+            0xC0 => CgbSupport::Only,
+            flag if flag & 0x80 != 0 => CgbSupport::Enhanced,
+            _ => CgbSupport::None,
+        };
+
+        // the CGB flag and the manufacturer code were carved out of the title
+        let manufacturer = &rom[MANUFACTURER_ADDR..CGB_FLAG_ADDR];
+        let has_manufacturer = cgb != CgbSupport::None
+            && manufacturer.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit());
+        let title_end = match (has_manufacturer, cgb) {
+            (true, _) => MANUFACTURER_ADDR,
+            (false, CgbSupport::None) => NEW_LICENSEE_ADDR,
+            (false, _) => CGB_FLAG_ADDR,
+        };
+
+        let old_licensee = rom[OLD_LICENSEE_ADDR];
+        let licensee = if old_licensee == USE_NEW_LICENSEE {
+            Licensee::New(ascii(&rom[NEW_LICENSEE_ADDR..SGB_FLAG_ADDR]))
+        } else {
+            Licensee::Old(old_licensee)
+        };
+
+        let type_code = rom[CARTRIDGE_TYPE_ADDR];
+        let cartridge_type = CartridgeType::from_code(type_code).ok_or(HeaderError::UnknownCartridgeType(type_code))?;
+
+        let rom_size = match rom[ROM_SIZE_ADDR] {
+            code @ 0x00..=0x08 => 0x8000 << code,
+            0x52 => 72 * 0x4000,
+            0x53 => 80 * 0x4000,
+            0x54 => 96 * 0x4000,
+            code => return Err(HeaderError::UnknownRomSize(code)),
+        };
+
+        let ram_size = match rom[RAM_SIZE_ADDR] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(HeaderError::UnknownRamSize(code)),
+        };
+
+        Ok(Self {
+            title: ascii(&rom[TITLE_ADDR..title_end]),
+            manufacturer: if has_manufacturer { Some(ascii(manufacturer)) } else { None },
+            cgb,
+            // the SGB functions are only unlocked for games using the new licensee
+            sgb: rom[SGB_FLAG_ADDR] == 0x03 && old_licensee == USE_NEW_LICENSEE,
+            licensee,
+            cartridge_type,
+            rom_size,
+            ram_size,
+            destination: if rom[DESTINATION_ADDR] == 0x00 { Destination::Japan } else { Destination::Overseas },
+            version: rom[VERSION_ADDR],
+            header_checksum: rom[HEADER_CHECKSUM_ADDR],
+            global_checksum: (rom[GLOBAL_CHECKSUM_ADDR] as u16) << 8 | rom[GLOBAL_CHECKSUM_ADDR + 1] as u16,
+        })
+    }
+
+    /// Whether `rom` starts with a header the boot ROM would accept: the
+    /// logo in place and the header checksum right
+    pub fn is_bootable(rom: &[u8]) -> bool {
+        rom.len() >= HEADER_END
+            && rom[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()] == NINTENDO_LOGO[..]
+            && header_checksum(rom) == rom[HEADER_CHECKSUM_ADDR]
+    }
+
+    /// Checks both checksums against the image the header came from
+    pub fn verify(&self, rom: &[u8]) -> Result<(), HeaderError> {
+        let header_checksum = header_checksum(rom);
+
+        if header_checksum != self.header_checksum {
+            return Err(HeaderError::HeaderChecksum { expected: header_checksum, actual: self.header_checksum });
+        }
+
+        // everything but the checksum bytes themselves
+        let global_checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|&(addr, _)| addr != GLOBAL_CHECKSUM_ADDR && addr != GLOBAL_CHECKSUM_ADDR + 1)
+            .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16));
+
+        if global_checksum != self.global_checksum {
+            return Err(HeaderError::GlobalChecksum { expected: global_checksum, actual: self.global_checksum });
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "title:        {}", self.title)?;
+        if let Some(ref manufacturer) = self.manufacturer {
+            writeln!(f, "manufacturer: {}", manufacturer)?;
+        }
+        match self.licensee {
+            Licensee::Old(code) => writeln!(f, "licensee:     ${:02X}", code)?,
+            Licensee::New(ref code) => writeln!(f, "licensee:     {}", code)?,
+        }
+        writeln!(f, "type:         ${:02X} ({:?})", self.cartridge_type.code, self.cartridge_type.mapper)?;
+        writeln!(f, "ROM:          {} KiB", self.rom_size / 1024)?;
+        writeln!(f, "RAM:          {} KiB", self.ram_size / 1024)?;
+        writeln!(f, "CGB:          {:?}", self.cgb)?;
+        writeln!(f, "SGB:          {}", self.sgb)?;
+        writeln!(f, "destination:  {:?}", self.destination)?;
+        write!(f, "version:      {}", self.version)
+    }
+}
+
+/// What the header checksum at $014D should be
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_ADDR..HEADER_CHECKSUM_ADDR]
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_sub(byte).wrapping_sub(1))
+}
+
+/// Header strings are upper case ASCII padded with zeros
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&byte| byte != 0x00)
+        .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '?' })
+        .collect::<String>()
+        .trim_end()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 32 KiB image with a CGB-enhanced MBC5 header and valid checksums
+    fn sample_rom() -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+        rom[TITLE_ADDR..TITLE_ADDR + 7].copy_from_slice(b"POKEMON");
+        rom[MANUFACTURER_ADDR..CGB_FLAG_ADDR].copy_from_slice(b"AAXE");
+        rom[CGB_FLAG_ADDR] = 0x80;
+        rom[NEW_LICENSEE_ADDR..SGB_FLAG_ADDR].copy_from_slice(b"01");
+        rom[SGB_FLAG_ADDR] = 0x03;
+        rom[CARTRIDGE_TYPE_ADDR] = 0x1B;
+        rom[RAM_SIZE_ADDR] = 0x03;
+        rom[DESTINATION_ADDR] = 0x01;
+        rom[OLD_LICENSEE_ADDR] = USE_NEW_LICENSEE;
+        rom[VERSION_ADDR] = 0x02;
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM_ADDR] = header_checksum(rom);
+
+        rom[GLOBAL_CHECKSUM_ADDR] = 0;
+        rom[GLOBAL_CHECKSUM_ADDR + 1] = 0;
+        let sum = rom.iter().fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+        rom[GLOBAL_CHECKSUM_ADDR] = (sum >> 8) as u8;
+        rom[GLOBAL_CHECKSUM_ADDR + 1] = sum as u8;
+    }
+
+    #[test]
+    fn parses_fields() {
+        let header = CartridgeHeader::parse(&sample_rom()).unwrap();
+
+        assert_eq!(header.title, "POKEMON");
+        assert_eq!(header.manufacturer, Some("AAXE".into()));
+        assert_eq!(header.cgb, CgbSupport::Enhanced);
+        assert!(header.sgb);
+        assert_eq!(header.licensee, Licensee::New("01".into()));
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc5);
+        assert!(header.cartridge_type.battery);
+        assert!(!header.cartridge_type.rumble);
+        assert_eq!(header.rom_size, 0x8000);
+        assert_eq!(header.ram_size, 0x8000);
+        assert_eq!(header.destination, Destination::Overseas);
+        assert_eq!(header.version, 2);
+    }
+
+    #[test]
+    fn old_titles_run_up_to_the_licensee() {
+        let mut rom = sample_rom();
+        rom[TITLE_ADDR..NEW_LICENSEE_ADDR].copy_from_slice(b"SUPER MARIOLAND\0");
+        rom[OLD_LICENSEE_ADDR] = 0x01;
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "SUPER MARIOLAND");
+        assert_eq!(header.manufacturer, None);
+        assert_eq!(header.cgb, CgbSupport::None);
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+        assert!(!header.sgb);
+    }
+
+    #[test]
+    fn checksums() {
+        let mut rom = sample_rom();
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.verify(&rom), Ok(()));
+
+        rom[0x4000] = 0xFF;
+        assert!(matches!(header.verify(&rom), Err(HeaderError::GlobalChecksum { .. })));
+
+        rom[TITLE_ADDR] = b'Q';
+        assert!(matches!(header.verify(&rom), Err(HeaderError::HeaderChecksum { .. })));
+    }
+
+    #[test]
+    fn bootable_needs_the_logo() {
+        let mut rom = sample_rom();
+        assert!(!CartridgeHeader::is_bootable(&rom));
+
+        rom[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(CartridgeHeader::is_bootable(&rom));
+
+        rom[TITLE_ADDR] = b'Q';
+        assert!(!CartridgeHeader::is_bootable(&rom));
+        assert!(!CartridgeHeader::is_bootable(&rom[..0x14F]));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert_eq!(CartridgeHeader::parse(&[0; 0x14F]), Err(HeaderError::TooShort { len: 0x14F }));
+
+        let mut rom = sample_rom();
+        rom[CARTRIDGE_TYPE_ADDR] = 0x04;
+        assert_eq!(CartridgeHeader::parse(&rom), Err(HeaderError::UnknownCartridgeType(0x04)));
+
+        let mut rom = sample_rom();
+        rom[ROM_SIZE_ADDR] = 0x09;
+        assert_eq!(CartridgeHeader::parse(&rom), Err(HeaderError::UnknownRomSize(0x09)));
+
+        let mut rom = sample_rom();
+        rom[RAM_SIZE_ADDR] = 0x06;
+        assert_eq!(CartridgeHeader::parse(&rom), Err(HeaderError::UnknownRamSize(0x06)));
+    }
+}
@@ -0,0 +1,103 @@
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+
+/// What the infrared receiver reads while it sees no light
+pub const IR_DARK: u8 = 0xC0;
+
+/// Hudson's HuC1: MBC1-like banking of up to 1 MiB of ROM and 32 KiB of
+/// RAM, and an infrared port mapped over RAM. The port isn't connected to
+/// anything, so it never sees light and what's sent goes nowhere.
+pub struct HuC1 {
+    /// Set by writing $0E to $0000-$1FFF, which maps the IR port
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl HuC1 {
+    pub fn new() -> Self {
+        Self {
+            ir_mode: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+        }
+    }
+}
+
+impl Mbc for HuC1 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ir_mode = val & 0x0F == 0x0E,
+            0x2000..=0x3FFF => {
+                self.rom_bank = val & 0x3F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x03,
+            _ => {}
+        }
+    }
+
+    /// Unlike the MBCs, RAM doesn't need enabling
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.ir_mode {
+            return IR_DARK;
+        }
+
+        read_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if self.ir_mode {
+            trace!("HuC1 IR LED {}", if val & 0x01 != 0 { "on" } else { "off" });
+            return false;
+        }
+
+        write_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr), val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ROM_BANK_SIZE;
+    use super::*;
+
+    #[test]
+    fn banks_rom_and_ram() {
+        let mut mbc = HuC1::new();
+        let mut ram = vec![0x00; 0x8000];
+
+        mbc.write_register(0x2000, 0x3F);
+        assert_eq!(mbc.rom_offset(0x4000), 0x3F * ROM_BANK_SIZE);
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x4000, 0x03);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0x6000], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x42);
+    }
+
+    #[test]
+    fn ir_port_replaces_ram() {
+        let mut mbc = HuC1::new();
+        let mut ram = vec![0x00; 0x2000];
+
+        mbc.write_register(0x0000, 0x0E);
+        mbc.write_ram(&mut ram, 0xA000, 0x01);
+        assert_eq!(ram[0], 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), IR_DARK);
+
+        mbc.write_register(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x00);
+    }
+}
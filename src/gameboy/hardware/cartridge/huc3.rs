@@ -0,0 +1,210 @@
+use super::huc1::IR_DARK;
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+
+/// T-cycles in a minute at normal speed
+const CYCLES_PER_MINUTE: usize = 60 * 4_194_304;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Hudson's HuC3: up to 2 MiB of ROM, 32 KiB of RAM, an infrared port
+/// and a clock that counts minutes and days. $0000-$1FFF selects what
+/// $A000-$BFFF maps: RAM, or the clock's command, result and status
+/// registers, or the IR port.
+///
+/// The clock is driven by emulated cycles and isn't saved with the RAM,
+/// as there's no save file format for it other emulators agree on: battery
+/// backed games find it reset to day 0, 00:00 on every start. The IR port
+/// is stubbed like HuC1's.
+pub struct HuC3 {
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+    /// Nibble sent back by the last clock read command
+    result: u8,
+    /// Clock memory address the read and write commands use
+    index: u8,
+    minutes: u16,
+    days: u16,
+    alarm_minutes: u16,
+    alarm_days: u16,
+    alarm_enabled: bool,
+    cycles: usize,
+}
+
+impl HuC3 {
+    pub fn new() -> Self {
+        Self {
+            mode: 0x00,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            result: 0x00,
+            index: 0x00,
+            minutes: 0,
+            days: 0,
+            alarm_minutes: 0,
+            alarm_days: 0,
+            alarm_enabled: false,
+            cycles: 0,
+        }
+    }
+
+    /// A command written to the clock: the upper nibble is the command,
+    /// the lower one its argument
+    fn command(&mut self, val: u8) {
+        let arg = val & 0x0F;
+        let index = self.index as usize;
+
+        match val >> 4 {
+            // read the nibble at the index and move on
+            0x1 => {
+                self.result = match index {
+                    0..=2 => nibble(self.minutes, index),
+                    3..=6 => nibble(self.days, index - 3),
+                    _ => 0x00,
+                };
+                self.index = self.index.wrapping_add(1);
+            }
+            // write the nibble at the index, and move on for 3
+            0x2 | 0x3 => {
+                match index {
+                    0..=2 => set_nibble(&mut self.minutes, index, arg),
+                    3..=6 => set_nibble(&mut self.days, index - 3, arg),
+                    0x58..=0x5A => set_nibble(&mut self.alarm_minutes, index - 0x58, arg),
+                    0x5B..=0x5E => set_nibble(&mut self.alarm_days, index - 0x5B, arg),
+                    0x5F => self.alarm_enabled = arg & 0x01 != 0,
+                    _ => debug!("HuC3 write of {:X} to clock memory ${:02X}", arg, index),
+                }
+                if val >> 4 == 0x3 {
+                    self.index = self.index.wrapping_add(1);
+                }
+            }
+            0x4 => self.index = self.index & 0xF0 | arg,
+            0x5 => self.index = self.index & 0x0F | arg << 4,
+            _ => debug!("HuC3 clock command ${:02X} ignored", val),
+        }
+    }
+}
+
+impl Mbc for HuC3 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.mode = val & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = val & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = val & 0x03,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        match self.mode {
+            0x00 | 0x0A => read_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr)),
+            0x0C => 0x80 | self.result,
+            // the clock is never busy
+            0x0D => 0x01,
+            0x0E => IR_DARK,
+            _ => 0xFF,
+        }
+    }
+
+    /// Mode $00 maps RAM read only
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        match self.mode {
+            0x0A => return write_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr), val),
+            0x0B => self.command(val),
+            0x0E => trace!("HuC3 IR LED {}", if val & 0x01 != 0 { "on" } else { "off" }),
+            _ => {}
+        }
+
+        false
+    }
+
+    fn step(&mut self, cycles: usize) {
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_MINUTE {
+            self.cycles -= CYCLES_PER_MINUTE;
+
+            self.minutes += 1;
+            if self.minutes >= MINUTES_PER_DAY {
+                self.minutes = 0;
+                self.days = (self.days + 1) & 0x0FFF;
+            }
+        }
+    }
+}
+
+fn nibble(val: u16, i: usize) -> u8 {
+    (val >> (i * 4)) as u8 & 0x0F
+}
+
+fn set_nibble(val: &mut u16, i: usize, nibble: u8) {
+    *val = *val & !(0x0F << (i * 4)) | (nibble as u16) << (i * 4);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the 7 nibbles of the clock through the command interface
+    fn read_clock(mbc: &mut HuC3, ram: &mut [u8]) -> (u16, u16) {
+        mbc.write_register(0x0000, 0x0B);
+        mbc.write_ram(ram, 0xA000, 0x40);
+        mbc.write_ram(ram, 0xA000, 0x50);
+
+        let mut nibbles = Vec::new();
+        for _ in 0..7 {
+            mbc.write_register(0x0000, 0x0B);
+            mbc.write_ram(ram, 0xA000, 0x10);
+            mbc.write_register(0x0000, 0x0C);
+            nibbles.push(mbc.read_ram(ram, 0xA000) as u16 & 0x0F);
+        }
+
+        let minutes = nibbles[0] | nibbles[1] << 4 | nibbles[2] << 8;
+        let days = nibbles[3] | nibbles[4] << 4 | nibbles[5] << 8 | nibbles[6] << 12;
+        (minutes, days)
+    }
+
+    #[test]
+    fn ram_modes() {
+        let mut mbc = HuC3::new();
+        let mut ram = vec![0x00; 0x8000];
+
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x01);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0x2000], 0x42);
+
+        mbc.write_register(0x0000, 0x00);
+        mbc.write_ram(&mut ram, 0xA000, 0x24);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x42);
+
+        mbc.write_register(0x0000, 0x0D);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x01);
+        mbc.write_register(0x0000, 0x0E);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), IR_DARK);
+    }
+
+    #[test]
+    fn clock_commands() {
+        let mut mbc = HuC3::new();
+        let mut ram = vec![0x00; 0x2000];
+
+        // set the index to 0 and write 2 days, 23:59 with write-and-advance
+        mbc.write_register(0x0000, 0x0B);
+        for &val in &[0x40, 0x50, 0x3F, 0x39, 0x35, 0x32, 0x30, 0x30, 0x30] {
+            mbc.write_ram(&mut ram, 0xA000, val);
+        }
+        assert_eq!(read_clock(&mut mbc, &mut ram), (1439, 2));
+
+        mbc.step(CYCLES_PER_MINUTE);
+        assert_eq!(read_clock(&mut mbc, &mut ram), (0, 3));
+    }
+}
@@ -0,0 +1,100 @@
+use super::frames::Frames;
+use super::rtc::Rtc;
+use super::ROM_BANK_SIZE;
+
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Called with the new state whenever a rumble motor turns on or off
+pub type RumbleCallback = Box<dyn FnMut(bool)>;
+
+/// A memory bank controller: the chip on the cartridge that decides which
+/// part of the ROM and RAM the CPU sees. The cartridge owns the memories and
+/// hands them in, so saving RAM doesn't have to go through the controller.
+pub trait Mbc {
+    /// Where a read from $0000-$7FFF lands in the ROM image. The cartridge
+    /// wraps it to the ROM size, as the unused bank bits aren't wired up.
+    fn rom_offset(&self, addr: u16) -> usize;
+
+    /// Reads from flash memory, on controllers that can map it over ROM
+    fn read_flash(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// A write to $0000-$7FFF, which goes to the controller's registers
+    fn write_register(&mut self, addr: u16, val: u8);
+
+    /// A read from $A000-$BFFF
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8;
+
+    /// A write to $A000-$BFFF. Returns whether it stored anything a
+    /// battery keeps, rather than being ignored or going to a register.
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool;
+
+    /// Advances whatever runs by itself on the cartridge, by T-cycles at
+    /// normal speed
+    fn step(&mut self, _cycles: usize) {}
+
+    /// The real time clock, on controllers that have one
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+
+    /// Only cartridges with a rumble motor ever call it
+    fn set_rumble_callback(&mut self, _callback: RumbleCallback) {}
+
+    /// Tilt on the X and Y axes in g, on cartridges with an accelerometer
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// What the camera sees, on cartridges with one
+    fn set_camera_frames(&mut self, _frames: Frames) {}
+}
+
+/// Offset of `addr` in a 16 KiB ROM bank
+pub fn rom_bank_offset(bank: usize, addr: u16) -> usize {
+    bank * ROM_BANK_SIZE + (addr as usize & (ROM_BANK_SIZE - 1))
+}
+
+/// Offset of `addr` in an 8 KiB RAM bank
+pub fn ram_bank_offset(bank: usize, addr: u16) -> usize {
+    bank * RAM_BANK_SIZE + (addr as usize & (RAM_BANK_SIZE - 1))
+}
+
+/// Reads RAM, which mirrors when smaller than what it's addressed with.
+/// Without any RAM the bus floats high.
+pub fn read_mirrored(ram: &[u8], offset: usize) -> u8 {
+    if ram.is_empty() {
+        0xFF
+    } else {
+        ram[offset % ram.len()]
+    }
+}
+
+/// Returns whether there was any RAM to write to
+pub fn write_mirrored(ram: &mut [u8], offset: usize, val: u8) -> bool {
+    if ram.is_empty() {
+        return false;
+    }
+
+    let len = ram.len();
+    ram[offset % len] = val;
+    true
+}
+
+/// 32 KiB of ROM wired straight to the bus, and optionally 8 KiB of RAM
+pub struct RomOnly;
+
+impl Mbc for RomOnly {
+    fn rom_offset(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn write_register(&mut self, _: u16, _: u8) {}
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        read_mirrored(ram, ram_bank_offset(0, addr))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        write_mirrored(ram, ram_bank_offset(0, addr), val)
+    }
+}
@@ -0,0 +1,225 @@
+use super::header::{LOGO_ADDR, NINTENDO_LOGO};
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+use super::ROM_BANK_SIZE;
+
+/// MBC1: up to 2 MiB of ROM and 32 KiB of RAM. A 5-bit register selects the
+/// ROM bank at $4000; a 2-bit one supplies the upper ROM bank bits or, in
+/// mode 1, the RAM bank and the bank mapped at $0000 too.
+///
+/// Multicarts (MBC1M) wire the 5-bit register with its top bit cut off, so
+/// the 2-bit register picks one of four 256 KiB games.
+pub struct Mbc1 {
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            bank1: 0x01,
+            bank2: 0x00,
+            mode: false,
+            multicart,
+        }
+    }
+
+    /// There's no way to tell a multicart from its header. They are 1 MiB,
+    /// and the games after the menu carry their own header with the logo.
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != 0x100000 {
+            return false;
+        }
+
+        let logo = &rom[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()];
+        let games = (1..4)
+            .map(|game| game * 0x10 * ROM_BANK_SIZE + LOGO_ADDR)
+            .filter(|&offset| &rom[offset..offset + NINTENDO_LOGO.len()] == logo)
+            .count();
+
+        games > 0
+    }
+
+    /// Where the 2-bit register lands in the bank number
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        if self.multicart {
+            0x0F
+        } else {
+            0x1F
+        }
+    }
+
+    /// Only mode 1 lets the 2-bit register bank RAM
+    fn ram_bank(&self) -> usize {
+        if self.mode {
+            self.bank2 as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let upper = (self.bank2 as usize) << self.bank2_shift();
+
+        let bank = match addr {
+            0x0000..=0x3FFF if self.mode => upper,
+            0x0000..=0x3FFF => 0,
+            _ => upper | (self.bank1 & self.bank1_mask()) as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // bank 0 can't be selected here: a zero reads as one, checked
+                // against all five bits even on multicarts
+                self.bank1 = val & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = val & 0x03,
+            _ => self.mode = val & 0x01 != 0,
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        read_mirrored(ram, ram_bank_offset(self.ram_bank(), addr))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        let offset = ram_bank_offset(self.ram_bank(), addr);
+        write_mirrored(ram, offset, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Which ROM bank `addr` reads from
+    fn bank(mbc: &Mbc1, addr: u16) -> usize {
+        mbc.rom_offset(addr) / ROM_BANK_SIZE
+    }
+
+    #[test]
+    fn selects_rom_banks() {
+        let mut mbc = Mbc1::new(false);
+        assert_eq!(bank(&mbc, 0x0000), 0);
+        assert_eq!(bank(&mbc, 0x4000), 1);
+        assert_eq!(mbc.rom_offset(0x7FFF), 0x7FFF);
+
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(bank(&mbc, 0x4000), 5);
+
+        // only the low five bits are wired
+        mbc.write_register(0x3FFF, 0xE7);
+        assert_eq!(bank(&mbc, 0x4000), 7);
+
+        mbc.write_register(0x4000, 0x02);
+        assert_eq!(bank(&mbc, 0x4000), 0x47);
+        assert_eq!(bank(&mbc, 0x0000), 0);
+    }
+
+    #[test]
+    fn bank_zero_reads_as_one() {
+        let mut mbc = Mbc1::new(false);
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(bank(&mbc, 0x4000), 1);
+
+        // the check only looks at the 5-bit register, so $20/$40/$60 can't be
+        // reached at $4000 either
+        mbc.write_register(0x4000, 0x01);
+        assert_eq!(bank(&mbc, 0x4000), 0x21);
+        mbc.write_register(0x2000, 0x20);
+        assert_eq!(bank(&mbc, 0x4000), 0x21);
+    }
+
+    #[test]
+    fn mode_one_banks_the_lower_area_and_ram() {
+        let mut mbc = Mbc1::new(false);
+        let mut ram = vec![0x00; 0x8000];
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x02);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x11);
+        assert_eq!(ram[0x0000], 0x11);
+        assert_eq!(bank(&mbc, 0x0000), 0);
+
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(bank(&mbc, 0x0000), 0x40);
+        mbc.write_ram(&mut ram, 0xA001, 0x22);
+        assert_eq!(ram[0x4001], 0x22);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x22);
+    }
+
+    #[test]
+    fn ram_needs_enabling() {
+        let mut mbc = Mbc1::new(false);
+        let mut ram = vec![0x00; 0x2000];
+
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(ram[0], 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        // only the low nibble counts
+        mbc.write_register(0x1234, 0xFA);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x12);
+
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn multicart_banking() {
+        let mut mbc = Mbc1::new(true);
+        mbc.write_register(0x2000, 0x13);
+        mbc.write_register(0x4000, 0x02);
+        assert_eq!(bank(&mbc, 0x4000), 0x23);
+
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(bank(&mbc, 0x0000), 0x20);
+
+        // a zero in the upper bit still counts as non-zero
+        mbc.write_register(0x2000, 0x10);
+        assert_eq!(bank(&mbc, 0x4000), 0x20);
+    }
+
+    #[test]
+    fn detects_multicarts() {
+        let mut rom = vec![0x00; 0x100000];
+        rom[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(!Mbc1::is_multicart(&rom));
+
+        let game = 0x20 * ROM_BANK_SIZE + LOGO_ADDR;
+        rom[game..game + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(Mbc1::is_multicart(&rom));
+
+        assert!(!Mbc1::is_multicart(&rom[..0x80000]));
+    }
+}
@@ -0,0 +1,118 @@
+use super::mbc::{rom_bank_offset, Mbc};
+
+/// 512 half-bytes of RAM built into the controller
+pub const RAM_SIZE: usize = 0x200;
+
+/// MBC2: up to 256 KiB of ROM and its own 512x4-bit RAM. It only decodes
+/// $0000-$3FFF, with address bit 8 choosing between the RAM enable and the
+/// ROM bank register.
+pub struct Mbc2 {
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enabled = val & 0x0F == 0x0A,
+            0x0000..=0x3FFF => {
+                self.rom_bank = val & 0x0F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Only the low nibble is wired, the upper one floats high. The 512
+    /// bytes repeat across the whole $A000-$BFFF area.
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        ram[addr as usize % RAM_SIZE] | 0xF0
+    }
+
+    /// Stored with the upper nibble set, so a save file holds what the game
+    /// would read back
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if self.ram_enabled {
+            ram[addr as usize % RAM_SIZE] = val | 0xF0;
+        }
+
+        self.ram_enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ROM_BANK_SIZE;
+    use super::*;
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut mbc = Mbc2::new();
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        // bit 8 clear: RAM enable, whatever the value
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2100, 0x05);
+        assert_eq!(mbc.rom_offset(0x4000), 5 * ROM_BANK_SIZE);
+        mbc.write_register(0x0100, 0xF3);
+        assert_eq!(mbc.rom_offset(0x4000), 3 * ROM_BANK_SIZE);
+
+        // bank 0 reads as 1
+        mbc.write_register(0x3F00, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        // $4000-$7FFF isn't decoded
+        mbc.write_register(0x4100, 0x07);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+        assert_eq!(mbc.rom_offset(0x3FFF), 0x3FFF);
+    }
+
+    #[test]
+    fn ram_is_four_bits_wide_and_mirrored() {
+        let mut mbc = Mbc2::new();
+        let mut ram = vec![0x00; RAM_SIZE];
+
+        mbc.write_ram(&mut ram, 0xA000, 0x05);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        // bit 8 set: a ROM bank write, which leaves RAM disabled
+        mbc.write_register(0x0100, 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x35);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xF5);
+        assert_eq!(mbc.read_ram(&ram, 0xA200), 0xF5);
+        assert_eq!(mbc.read_ram(&ram, 0xBE00), 0xF5);
+
+        mbc.write_ram(&mut ram, 0xB1FF, 0x0C);
+        assert_eq!(mbc.read_ram(&ram, 0xA1FF), 0xFC);
+        assert_eq!(ram[0x1FF], 0xFC);
+    }
+}
@@ -0,0 +1,167 @@
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+use super::rtc::Rtc;
+
+/// MBC3: up to 2 MiB of ROM, 32 KiB of RAM and optionally a real time
+/// clock, whose registers are mapped in place of a RAM bank. MBC30, as in
+/// the Japanese Crystal, has an extra bank bit for each, which costs
+/// nothing to support.
+pub struct Mbc3 {
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// $00-$07 selects a RAM bank, $08-$0C an RTC register
+    ram_bank: u8,
+    /// The last write to the latch register, a $00 arming the next $01
+    latch: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(timer: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x01,
+            ram_bank: 0x00,
+            latch: 0xFF,
+            rtc: if timer { Some(Rtc::new()) } else { None },
+        }
+    }
+
+    /// The RTC register selected instead of RAM, if any
+    fn rtc_register(&self) -> Option<u8> {
+        match self.ram_bank {
+            0x08..=0x0C if self.rtc.is_some() => Some(self.ram_bank),
+            _ => None,
+        }
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = if val == 0 { 1 } else { val },
+            0x4000..=0x5FFF => self.ram_bank = val & 0x0F,
+            _ => {
+                if self.latch == 0x00 && val == 0x01 {
+                    if let Some(ref mut rtc) = self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch = val;
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match (self.rtc_register(), &self.rtc) {
+            (Some(reg), Some(rtc)) => rtc.read(reg),
+            _ if self.ram_bank < 0x08 => read_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr)),
+            _ => 0xFF,
+        }
+    }
+
+    /// The clock is saved along with RAM, so writes to it count too
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        match (self.rtc_register(), &mut self.rtc) {
+            (Some(reg), Some(rtc)) => {
+                rtc.write(reg, val);
+                true
+            }
+            _ if self.ram_bank < 0x08 => write_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr), val),
+            _ => false,
+        }
+    }
+
+    fn step(&mut self, cycles: usize) {
+        if let Some(ref mut rtc) = self.rtc {
+            rtc.step(cycles);
+        }
+    }
+
+    fn rtc(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rtc::RtcClock;
+    use super::super::ROM_BANK_SIZE;
+    use super::*;
+
+    #[test]
+    fn selects_seven_and_eight_bit_rom_banks() {
+        let mut mbc = Mbc3::new(false);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0x7F);
+        assert_eq!(mbc.rom_offset(0x4000), 0x7F * ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0xFF);
+        assert_eq!(mbc.rom_offset(0x4000), 0xFF * ROM_BANK_SIZE);
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+    }
+
+    #[test]
+    fn banks_ram() {
+        let mut mbc = Mbc3::new(false);
+        let mut ram = vec![0x00; 0x8000];
+        mbc.write_register(0x0000, 0x0A);
+
+        mbc.write_register(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0xA010, 0x42);
+        assert_eq!(ram[0x4010], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA010), 0x42);
+
+        // without a clock its registers aren't there
+        mbc.write_register(0x4000, 0x08);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn maps_latched_rtc_registers() {
+        let mut mbc = Mbc3::new(true);
+        let mut ram = vec![0x00; 0x2000];
+        mbc.rtc().unwrap().set_clock(RtcClock::Emulated);
+        mbc.write_register(0x0000, 0x0A);
+
+        mbc.write_register(0x4000, 0x08);
+        mbc.write_ram(&mut ram, 0xA000, 30);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 30);
+        assert_eq!(ram[0], 0x00);
+
+        mbc.step(4_194_304 * 2);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 30);
+
+        // a $01 only latches right after a $00
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 30);
+        mbc.write_register(0x6000, 0x00);
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xBFFF), 32);
+
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+}
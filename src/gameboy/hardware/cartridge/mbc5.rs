@@ -0,0 +1,140 @@
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc, RumbleCallback};
+
+/// MBC5: up to 8 MiB of ROM through a 9-bit bank register, and 128 KiB of
+/// RAM. Unlike the earlier controllers it lets bank 0 be mapped at $4000
+/// too. On rumble cartridges bit 3 of the RAM bank register drives the
+/// motor instead.
+pub struct Mbc5 {
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumble: bool,
+    motor: bool,
+    on_rumble: Option<RumbleCallback>,
+}
+
+impl Mbc5 {
+    pub fn new(rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 0x001,
+            ram_bank: 0x00,
+            rumble,
+            motor: false,
+            on_rumble: None,
+        }
+    }
+
+    fn set_motor(&mut self, on: bool) {
+        if on == self.motor {
+            return;
+        }
+
+        self.motor = on;
+        if let Some(ref mut callback) = self.on_rumble {
+            callback(on);
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = self.rom_bank & 0x100 | val as u16,
+            0x3000..=0x3FFF => self.rom_bank = self.rom_bank & 0x0FF | (val as u16 & 0x01) << 8,
+            0x4000..=0x5FFF if self.rumble => {
+                self.ram_bank = val & 0x07;
+                self.set_motor(val & 0x08 != 0);
+            }
+            0x4000..=0x5FFF => self.ram_bank = val & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        read_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        self.ram_enabled && write_mirrored(ram, ram_bank_offset(self.ram_bank as usize, addr), val)
+    }
+
+    fn set_rumble_callback(&mut self, callback: RumbleCallback) {
+        self.on_rumble = Some(callback);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ROM_BANK_SIZE;
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn nine_bit_rom_bank_including_zero() {
+        let mut mbc = Mbc5::new(false);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), 0);
+
+        mbc.write_register(0x3000, 0xFF);
+        assert_eq!(mbc.rom_offset(0x4000), 0x100 * ROM_BANK_SIZE);
+
+        mbc.write_register(0x2FFF, 0xFF);
+        assert_eq!(mbc.rom_offset(0x7FFF), 0x1FF * ROM_BANK_SIZE + 0x3FFF);
+
+        mbc.write_register(0x3FFF, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), 0xFF * ROM_BANK_SIZE);
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(false);
+        let mut ram = vec![0x00; 0x20000];
+        mbc.write_register(0x0000, 0x0A);
+
+        mbc.write_register(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0xBFFF, 0x42);
+        assert_eq!(ram[0x1FFFF], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xBFFF), 0x42);
+
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xBFFF), 0xFF);
+    }
+
+    #[test]
+    fn reports_rumble_changes() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let log = events.clone();
+        let mut mbc = Mbc5::new(true);
+        let mut ram = vec![0x00; 0x8000];
+        mbc.set_rumble_callback(Box::new(move |on| log.borrow_mut().push(on)));
+        mbc.write_register(0x0000, 0x0A);
+
+        mbc.write_register(0x4000, 0x0B);
+        mbc.write_register(0x4000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0x4000], 0x42);
+
+        mbc.write_register(0x4000, 0x02);
+        assert_eq!(*events.borrow(), vec![true, false]);
+    }
+}
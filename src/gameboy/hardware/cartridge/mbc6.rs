@@ -0,0 +1,124 @@
+use super::mbc::{read_mirrored, write_mirrored, Mbc};
+
+/// MBC6 banks ROM in 8 KiB halves of the switchable area
+const ROM_HALF_BANK_SIZE: usize = 0x2000;
+
+/// ...and RAM in 4 KiB halves of $A000-$BFFF
+const RAM_HALF_BANK_SIZE: usize = 0x1000;
+
+/// MBC6, only used by Net de Get: $4000-$5FFF and $6000-$7FFF are banked
+/// separately, each mapping either ROM or a 1 MiB flash chip, and so are
+/// the two halves of RAM.
+///
+/// The flash chip is left as it came from the factory: erased, reading
+/// $FF and ignoring the commands that would program it. The game only
+/// keeps downloaded minigames there.
+pub struct Mbc6 {
+    ram_enabled: bool,
+    ram_banks: [u8; 2],
+    rom_banks: [u8; 2],
+    /// Which of the two ROM windows show flash instead
+    flash: [bool; 2],
+}
+
+impl Mbc6 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            ram_banks: [0x00; 2],
+            rom_banks: [0x00; 2],
+            flash: [false; 2],
+        }
+    }
+
+    /// 0 for the lower window of the switchable area, 1 for the upper one
+    fn window(addr: u16) -> usize {
+        (addr as usize >> 13) & 0x01
+    }
+}
+
+impl Mbc for Mbc6 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => addr as usize,
+            _ => {
+                let bank = self.rom_banks[Self::window(addr)] as usize;
+                bank * ROM_HALF_BANK_SIZE + (addr as usize & (ROM_HALF_BANK_SIZE - 1))
+            }
+        }
+    }
+
+    fn read_flash(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4000..=0x7FFF if self.flash[Self::window(addr)] => Some(0xFF),
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x03FF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_banks[0] = val & 0x07,
+            0x0800..=0x0BFF => self.ram_banks[1] = val & 0x07,
+            0x2000..=0x27FF => self.rom_banks[0] = val & 0x7F,
+            0x2800..=0x2FFF => self.flash[0] = val == 0x08,
+            0x3000..=0x37FF => self.rom_banks[1] = val & 0x7F,
+            0x3800..=0x3FFF => self.flash[1] = val == 0x08,
+            _ => debug!("MBC6 flash register write of ${:02X} to ${:04X} ignored", val, addr),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        let bank = self.ram_banks[(addr as usize >> 12) & 0x01] as usize;
+        read_mirrored(ram, bank * RAM_HALF_BANK_SIZE + (addr as usize & (RAM_HALF_BANK_SIZE - 1)))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        let bank = self.ram_banks[(addr as usize >> 12) & 0x01] as usize;
+        write_mirrored(ram, bank * RAM_HALF_BANK_SIZE + (addr as usize & (RAM_HALF_BANK_SIZE - 1)), val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banks_each_half_separately() {
+        let mut mbc = Mbc6::new();
+        mbc.write_register(0x2000, 0x05);
+        mbc.write_register(0x3000, 0x7E);
+        assert_eq!(mbc.rom_offset(0x4123), 5 * 0x2000 + 0x0123);
+        assert_eq!(mbc.rom_offset(0x6123), 0x7E * 0x2000 + 0x0123);
+        assert_eq!(mbc.rom_offset(0x3FFF), 0x3FFF);
+
+        let mut ram = vec![0x00; 0x8000];
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x0400, 0x03);
+        mbc.write_register(0x0800, 0x07);
+        mbc.write_ram(&mut ram, 0xA001, 0x12);
+        mbc.write_ram(&mut ram, 0xB001, 0x34);
+        assert_eq!(ram[0x3001], 0x12);
+        assert_eq!(ram[0x7001], 0x34);
+        assert_eq!(mbc.read_ram(&ram, 0xB001), 0x34);
+    }
+
+    #[test]
+    fn flash_reads_erased() {
+        let mut mbc = Mbc6::new();
+        mbc.write_register(0x3800, 0x08);
+        assert_eq!(mbc.read_flash(0x4000), None);
+        assert_eq!(mbc.read_flash(0x7FFF), Some(0xFF));
+
+        mbc.write_register(0x3800, 0x00);
+        assert_eq!(mbc.read_flash(0x7FFF), None);
+    }
+}
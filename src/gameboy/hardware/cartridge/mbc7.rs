@@ -0,0 +1,168 @@
+use super::eeprom::Eeprom;
+use super::mbc::{rom_bank_offset, Mbc};
+
+/// What the accelerometer reads when level
+const CENTER: u16 = 0x81D0;
+
+/// How far one g of tilt moves the reading
+const ONE_G: f32 = 112.0;
+
+/// MBC7: up to 2 MiB of ROM, a two-axis accelerometer and a 93LC56 EEPROM
+/// in place of RAM. Both sit in $A000-$AFFF once two separate enables are
+/// set, with address bits 4-7 picking the register.
+pub struct Mbc7 {
+    rom_bank: u8,
+    ram_enabled: bool,
+    ram_enabled2: bool,
+    eeprom: Eeprom,
+    /// The tilt the host last reported, in g
+    tilt: (f32, f32),
+    /// Readings held for the game, set by the latch sequence
+    latched: (u16, u16),
+    /// Set once the latch is erased with $55, so the next $AA latches
+    latch_armed: bool,
+}
+
+impl Mbc7 {
+    pub fn new() -> Self {
+        Self {
+            rom_bank: 0x01,
+            ram_enabled: false,
+            ram_enabled2: false,
+            eeprom: Eeprom::new(),
+            tilt: (0.0, 0.0),
+            latched: (0x8000, 0x8000),
+            latch_armed: false,
+        }
+    }
+
+    fn enabled(&self, addr: u16) -> bool {
+        self.ram_enabled && self.ram_enabled2 && addr < 0xB000
+    }
+}
+
+impl Mbc for Mbc7 {
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = val & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = val & 0x7F,
+            0x4000..=0x5FFF => self.ram_enabled2 = val == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, _ram: &[u8], addr: u16) -> u8 {
+        if !self.enabled(addr) {
+            return 0xFF;
+        }
+
+        match addr & 0x00F0 {
+            0x20 => self.latched.0 as u8,
+            0x30 => (self.latched.0 >> 8) as u8,
+            0x40 => self.latched.1 as u8,
+            0x50 => (self.latched.1 >> 8) as u8,
+            0x60 => 0x00,
+            0x80 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    /// Only what the EEPROM stores counts as written
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.enabled(addr) {
+            return false;
+        }
+
+        match addr & 0x00F0 {
+            0x00 if val == 0x55 => {
+                self.latched = (0x8000, 0x8000);
+                self.latch_armed = true;
+            }
+            0x10 if val == 0xAA && self.latch_armed => {
+                let reading = |g: f32| (CENTER as f32 + g * ONE_G).max(0.0).min(0xFFFF as f32) as u16;
+                self.latched = (reading(self.tilt.0), reading(self.tilt.1));
+                self.latch_armed = false;
+            }
+            0x80 => return self.eeprom.write(ram, val),
+            _ => {}
+        }
+
+        false
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> Mbc7 {
+        let mut mbc = Mbc7::new();
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x40);
+        mbc
+    }
+
+    fn reading(mbc: &Mbc7, ram: &[u8]) -> (u16, u16) {
+        let word = |addr| (mbc.read_ram(ram, addr + 0x10) as u16) << 8 | mbc.read_ram(ram, addr) as u16;
+        (word(0xA020), word(0xA040))
+    }
+
+    #[test]
+    fn needs_both_enables() {
+        let mut mbc = Mbc7::new();
+        let ram = vec![0x00; 0x100];
+        assert_eq!(mbc.read_ram(&ram, 0xA060), 0xFF);
+
+        mbc.write_register(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA060), 0xFF);
+        mbc.write_register(0x4000, 0x40);
+        assert_eq!(mbc.read_ram(&ram, 0xA060), 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xB060), 0xFF);
+    }
+
+    #[test]
+    fn latches_the_tilt() {
+        let mut mbc = enabled();
+        let mut ram = vec![0x00; 0x100];
+        mbc.set_tilt(1.0, -0.5);
+
+        // $AA alone doesn't latch
+        mbc.write_ram(&mut ram, 0xA010, 0xAA);
+        assert_eq!(reading(&mbc, &ram), (0x8000, 0x8000));
+
+        // latching isn't something to save
+        assert!(!mbc.write_ram(&mut ram, 0xA000, 0x55));
+        assert!(!mbc.write_ram(&mut ram, 0xA010, 0xAA));
+        assert_eq!(reading(&mbc, &ram), (CENTER + 112, CENTER - 56));
+
+        // the reading holds until the next latch
+        mbc.set_tilt(0.0, 0.0);
+        assert_eq!(reading(&mbc, &ram), (CENTER + 112, CENTER - 56));
+        mbc.write_ram(&mut ram, 0xA000, 0x55);
+        assert_eq!(reading(&mbc, &ram), (0x8000, 0x8000));
+        mbc.write_ram(&mut ram, 0xA010, 0xAA);
+        assert_eq!(reading(&mbc, &ram), (CENTER, CENTER));
+    }
+
+    #[test]
+    fn rom_bank_zero_is_selectable() {
+        let mut mbc = Mbc7::new();
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), 0);
+        mbc.write_register(0x2000, 0x85);
+        assert_eq!(mbc.rom_offset(0x4000), 5 * 0x4000);
+    }
+}
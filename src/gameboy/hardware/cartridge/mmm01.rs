@@ -0,0 +1,250 @@
+use super::header::CartridgeHeader;
+use super::mbc::{ram_bank_offset, read_mirrored, rom_bank_offset, write_mirrored, Mbc};
+
+/// Where the menu, and the header describing the whole cartridge, sits:
+/// the last 32 KiB of ROM
+pub const MENU_SIZE: usize = 0x8000;
+
+/// Header cartridge type codes of MMM01, without and with RAM and battery
+const TYPES: [u8; 3] = [0x0B, 0x0C, 0x0D];
+
+/// MMM01, a multicart controller. It starts out with the menu in the last
+/// 32 KiB of ROM mapped at $0000-$7FFF. The menu sets up which part of the
+/// ROM and RAM the chosen game gets and then locks the outer bank bits,
+/// after which the controller behaves much like an MBC1 for the game.
+///
+/// The multiplex bit, which swaps RAM and ROM bank bits around for games
+/// that want it, isn't emulated.
+pub struct Mmm01 {
+    mapped: bool,
+    ram_enabled: bool,
+    /// Bits 0-4 of the ROM bank, the part a game switches
+    rom_low: u8,
+    /// Bits 5-6 and 7-8 of the ROM bank, the game's place in the ROM
+    rom_mid: u8,
+    rom_high: u8,
+    /// Bits of `rom_low` the game can't change, in bits 1-4
+    rom_mask: u8,
+    ram_low: u8,
+    ram_high: u8,
+    mode: bool,
+    mode_locked: bool,
+}
+
+impl Mmm01 {
+    pub fn new() -> Self {
+        Self {
+            mapped: false,
+            ram_enabled: false,
+            rom_low: 0x00,
+            rom_mid: 0x00,
+            rom_high: 0x00,
+            rom_mask: 0x00,
+            ram_low: 0x00,
+            ram_high: 0x00,
+            mode: false,
+            mode_locked: false,
+        }
+    }
+
+    /// MMM01 dumps start with the first game, so it's the menu's header at
+    /// the end of the ROM that tells them apart. In any other ROM that's
+    /// just code, so the whole header has to check out, not only the type.
+    pub fn is_mmm01(rom: &[u8]) -> bool {
+        if rom.len() <= MENU_SIZE {
+            return false;
+        }
+
+        let menu = Self::menu(rom);
+        CartridgeHeader::is_bootable(menu) && TYPES.contains(&menu[0x0147])
+    }
+
+    /// The last 32 KiB, where the menu and its header are
+    pub fn menu(rom: &[u8]) -> &[u8] {
+        &rom[rom.len() - MENU_SIZE..]
+    }
+
+    fn outer_bank(&self) -> usize {
+        (self.rom_high as usize) << 7 | (self.rom_mid as usize) << 5
+    }
+
+    /// As on MBC1, the low bits only bank RAM in mode 1
+    fn ram_bank(&self) -> usize {
+        let low = if self.mode { self.ram_low } else { 0 };
+        (self.ram_high << 2 | low) as usize
+    }
+}
+
+impl Mbc for Mmm01 {
+    /// Before mapping, every bank bit but the lowest reads as set, which
+    /// the cartridge wraps around to the last 32 KiB
+    fn rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x0000..=0x3FFF if !self.mapped => 0x1FE,
+            _ if !self.mapped => 0x1FF,
+            0x0000..=0x3FFF => self.outer_bank() | (self.rom_low & self.rom_mask) as usize,
+            _ => {
+                // the zero check only looks at the bits the game controls
+                let low = if self.rom_low & !self.rom_mask == 0 {
+                    self.rom_low | 0x01
+                } else {
+                    self.rom_low
+                };
+                self.outer_bank() | low as usize
+            }
+        };
+
+        rom_bank_offset(bank, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = val & 0x0F == 0x0A;
+                if val & 0x40 != 0 && !self.mapped {
+                    debug!("MMM01 mapped ROM bank ${:03X}", self.outer_bank());
+                    self.mapped = true;
+                }
+            }
+            0x2000..=0x3FFF => {
+                self.rom_low = self.rom_low & self.rom_mask | val & 0x1F & !self.rom_mask;
+                if !self.mapped {
+                    self.rom_mid = (val >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.ram_low = val & 0x03;
+                if !self.mapped {
+                    self.ram_high = (val >> 2) & 0x03;
+                    self.rom_high = (val >> 4) & 0x03;
+                    self.mode_locked = val & 0x40 != 0;
+                }
+            }
+            _ => {
+                if !self.mode_locked {
+                    self.mode = val & 0x01 != 0;
+                }
+                if !self.mapped {
+                    self.rom_mask = (val >> 1) & 0x1E;
+                    if val & 0x40 != 0 {
+                        warn!("MMM01 multiplexing isn't emulated");
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        read_mirrored(ram, ram_bank_offset(self.ram_bank(), addr))
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        let bank = self.ram_bank();
+        write_mirrored(ram, ram_bank_offset(bank, addr), val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::header::{header_checksum, LOGO_ADDR, NINTENDO_LOGO};
+    use super::super::{Cartridge, Mapper, ROM_BANK_SIZE};
+    use super::*;
+
+    /// Gives `rom` a menu of type `code` with a header the boot ROM takes
+    fn add_menu(rom: &mut [u8], code: u8) {
+        let start = rom.len() - MENU_SIZE;
+        let menu = &mut rom[start..];
+        menu[LOGO_ADDR..LOGO_ADDR + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        menu[0x0147] = code;
+        menu[0x014D] = header_checksum(menu);
+    }
+
+    /// Which ROM bank `addr` reads from, before wrapping to the ROM size
+    fn bank(mbc: &Mmm01, addr: u16) -> usize {
+        mbc.rom_offset(addr) / ROM_BANK_SIZE
+    }
+
+    #[test]
+    fn boots_into_the_menu() {
+        let mbc = Mmm01::new();
+        // 64 banks of 16 KiB: banks $3E and $3F
+        assert_eq!(bank(&mbc, 0x0000) % 64, 0x3E);
+        assert_eq!(bank(&mbc, 0x4000) % 64, 0x3F);
+    }
+
+    #[test]
+    fn menu_maps_a_game() {
+        let mut mbc = Mmm01::new();
+
+        // game at bank $20, 4 banks long: bits 2-4 of the low bank locked
+        mbc.write_register(0x2000, 0x20);
+        mbc.write_register(0x4000, 0x04);
+        mbc.write_register(0x6000, 0x38);
+        mbc.write_register(0x0000, 0x40);
+        assert_eq!(bank(&mbc, 0x0000), 0x20);
+        assert_eq!(bank(&mbc, 0x4000), 0x21);
+
+        mbc.write_register(0x2000, 0x03);
+        assert_eq!(bank(&mbc, 0x4000), 0x23);
+
+        // the outer bits are locked now
+        mbc.write_register(0x2000, 0x7F);
+        mbc.write_register(0x4000, 0x3C);
+        assert_eq!(bank(&mbc, 0x4000), 0x23);
+        assert_eq!(bank(&mbc, 0x0000), 0x20);
+
+        // the game's RAM starts at bank 4
+        let mut ram = vec![0x00; 0x20000];
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0x8000], 0x42);
+    }
+
+    #[test]
+    fn detected_from_the_menu_header() {
+        let mut rom = vec![0x00; 0x20000];
+        assert!(!Mmm01::is_mmm01(&rom));
+
+        add_menu(&mut rom, 0x0D);
+        assert!(Mmm01::is_mmm01(&rom));
+
+        // a broken header checksum means it's not a header after all
+        rom[0x20000 - MENU_SIZE + 0x0134] = b'X';
+        assert!(!Mmm01::is_mmm01(&rom));
+    }
+
+    #[test]
+    fn type_code_alone_is_just_code() {
+        // $0C is INC C, and as likely as any byte in another game's code
+        let mut rom = vec![0x00; 0x20000];
+        rom[0x0147] = 0x03;
+        rom[0x20000 - MENU_SIZE + 0x0147] = 0x0C;
+        assert!(!Mmm01::is_mmm01(&rom));
+
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        assert_eq!(cartridge.header().cartridge_type.mapper, Mapper::Mbc1);
+    }
+
+    #[test]
+    fn checksums_come_from_the_menu() {
+        // the first game's header is left blank, so its checksums are off
+        let mut rom = vec![0x00; 0x20000];
+        rom[0x1000] = 0x42;
+        add_menu(&mut rom, 0x0B);
+        let sum = rom[0x20000 - MENU_SIZE..].iter().fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+        rom[0x20000 - MENU_SIZE + 0x014E] = (sum >> 8) as u8;
+        rom[0x20000 - MENU_SIZE + 0x014F] = sum as u8;
+
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        assert_eq!(cartridge.header().cartridge_type.mapper, Mapper::Mmm01);
+        assert_eq!(cartridge.verify(), Ok(()));
+    }
+}
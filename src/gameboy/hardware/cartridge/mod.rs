@@ -1,16 +1,362 @@
+use super::error::{EmuError, Result};
+
+pub use self::frames::{Frame, Frames};
+pub use self::header::{CartridgeHeader, CartridgeType, CgbSupport, Destination, HeaderError, Licensee, Mapper};
+pub use self::mbc::{Mbc, RumbleCallback};
+pub use self::rtc::RtcClock;
+
+use self::mbc::RomOnly;
+use self::mbc1::Mbc1;
+use self::mbc2::Mbc2;
+use self::mbc3::Mbc3;
+use self::camera::Camera;
+use self::huc1::HuC1;
+use self::huc3::HuC3;
+use self::mbc5::Mbc5;
+use self::mbc6::Mbc6;
+use self::mbc7::Mbc7;
+use self::mmm01::Mmm01;
+
+mod camera;
+mod eeprom;
+mod frames;
+mod header;
+mod huc1;
+mod huc3;
+mod mbc;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod rtc;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
 pub struct Cartridge {
+    header: CartridgeHeader,
+    mbc: Box<dyn Mbc>,
     rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    /// Whether a write to $A000-$BFFF stored anything since
+    /// `take_ram_written`
+    ram_written: bool,
 }
 
 impl Cartridge {
-    pub fn new(rom: Box<[u8]>) -> Self {
-        Self {
+    /// Fails only if the header can't be made sense of; checksum mismatches
+    /// are left to `verify`, as plenty of homebrew and patched ROMs have them
+    pub fn new(rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        let header = CartridgeHeader::parse(header_rom(&rom))?;
+
+        // MBC2 has its RAM on the controller and MBC7 an EEPROM, so the
+        // header declares none
+        let (mbc, ram_size): (Box<dyn Mbc>, usize) = match header.cartridge_type.mapper {
+            Mapper::RomOnly => (Box::new(RomOnly), header.ram_size),
+            Mapper::Mbc1 => (Box::new(Mbc1::new(Mbc1::is_multicart(&rom))), header.ram_size),
+            Mapper::Mbc2 => (Box::new(Mbc2::new()), mbc2::RAM_SIZE),
+            Mapper::Mbc3 => (Box::new(Mbc3::new(header.cartridge_type.timer)), header.ram_size),
+            Mapper::Mbc5 => (Box::new(Mbc5::new(header.cartridge_type.rumble)), header.ram_size),
+            Mapper::Mbc6 => (Box::new(Mbc6::new()), header.ram_size),
+            Mapper::Mbc7 => (Box::new(Mbc7::new()), eeprom::SIZE),
+            Mapper::HuC1 => (Box::new(HuC1::new()), header.ram_size),
+            Mapper::HuC3 => (Box::new(HuC3::new()), header.ram_size),
+            Mapper::Mmm01 => (Box::new(Mmm01::new()), header.ram_size),
+            Mapper::PocketCamera => (Box::new(Camera::new()), header.ram_size),
+            mapper => {
+                warn!("{:?} isn't emulated, running the cartridge without banking", mapper);
+                (Box::new(RomOnly), header.ram_size)
+            }
+        };
+
+        Ok(Self {
+            mbc,
+            ram: vec![0xFF; ram_size].into_boxed_slice(),
+            header,
             rom,
+            ram_written: false,
+        })
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    pub fn verify(&self) -> ::std::result::Result<(), HeaderError> {
+        self.header.verify(header_rom(&self.rom))
+    }
+
+    /// Number of 16 KiB ROM banks, counting a trailing partial one
+    pub fn rom_banks(&self) -> usize {
+        self.rom.len().div_ceil(ROM_BANK_SIZE)
+    }
+
+    /// The contents of a ROM bank, shorter than `ROM_BANK_SIZE` only for a
+    /// truncated last bank
+    pub fn rom_bank(&self, bank: usize) -> &[u8] {
+        let start = bank * ROM_BANK_SIZE;
+        let end = (start + ROM_BANK_SIZE).min(self.rom.len());
+
+        &self.rom[start..end]
+    }
+
+    /// Reads $0000-$7FFF. Bank numbers wrap at the size the header gives,
+    /// so only an image shorter than that can fault.
+    pub fn read(&self, addr: u16) -> Result<u8> {
+        if let Some(val) = self.mbc.read_flash(addr) {
+            return Ok(val);
+        }
+
+        let offset = self.mbc.rom_offset(addr) % self.header.rom_size;
+
+        self.rom.get(offset).cloned().ok_or(EmuError::CartridgeFault {
+            addr,
+            reason: "read past the end of the ROM",
+        })
+    }
+
+    /// Writes to $0000-$7FFF set the controller's registers
+    pub fn write(&mut self, addr: u16, val: u8) {
+        self.mbc.write_register(addr, val)
+    }
+
+    /// Reads $A000-$BFFF
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, addr)
+    }
+
+    pub fn write_ram(&mut self, addr: u16, val: u8) {
+        if self.mbc.write_ram(&mut self.ram, addr, val) {
+            self.ram_written = true;
+        }
+    }
+
+    /// Whether RAM, or the clock, survives power off
+    pub fn has_battery(&self) -> bool {
+        self.header.cartridge_type.battery
+    }
+
+    /// Whether the game wrote to RAM since the last call. Writes the
+    /// controller ignored, or took as a register, don't count.
+    pub fn take_ram_written(&mut self) -> bool {
+        let written = self.ram_written;
+        self.ram_written = false;
+        written
+    }
+
+    /// Advances the cartridge by T-cycles at normal speed
+    pub fn step(&mut self, cycles: usize) {
+        self.mbc.step(cycles)
+    }
+
+    /// Picks what drives the real time clock, if the cartridge has one
+    pub fn set_rtc_clock(&mut self, clock: RtcClock) {
+        if let Some(rtc) = self.mbc.rtc() {
+            rtc.set_clock(clock);
+        }
+    }
+
+    /// Has `callback` told about the rumble motor turning on and off
+    pub fn set_rumble_callback<F: FnMut(bool) + 'static>(&mut self, callback: F) {
+        self.mbc.set_rumble_callback(Box::new(callback))
+    }
+
+    /// Tilts the cartridge by `x` and `y` g, if it has an accelerometer.
+    /// Positive values tilt it right and towards the player.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mbc.set_tilt(x, y)
+    }
+
+    /// Points the camera, if the cartridge has one, at pictures from the
+    /// host
+    pub fn set_camera_frames(&mut self, frames: Frames) {
+        self.mbc.set_camera_frames(frames)
+    }
+
+    /// What a battery keeps alive: the RAM, followed by the clock in the
+    /// trailer format BGB and VBA-M use
+    pub fn save_data(&mut self) -> Vec<u8> {
+        let mut data = self.ram.to_vec();
+        if let Some(rtc) = self.mbc.rtc() {
+            data.extend_from_slice(&rtc.save());
         }
+
+        data
+    }
+
+    /// Restores what `save_data` returned, or a save file written by
+    /// another emulator. A missing clock trailer leaves the clock as is.
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+
+        let trailer = &data[len..];
+        let loaded = match self.mbc.rtc() {
+            Some(rtc) => trailer.is_empty() || rtc.load(trailer),
+            None => trailer.is_empty(),
+        };
+        if data.len() < self.ram.len() || !loaded {
+            warn!(
+                "save data is {} bytes, expected {} bytes of RAM and maybe a clock",
+                data.len(),
+                self.ram.len()
+            );
+        }
+    }
+}
+
+/// The part of the image the header is in: the start, or for MMM01 the
+/// menu at the end
+fn header_rom(rom: &[u8]) -> &[u8] {
+    if Mmm01::is_mmm01(rom) {
+        Mmm01::menu(rom)
+    } else {
+        rom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_is_addressed_from_zero() {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0000] = 0x12;
+        rom[0x0100] = 0x34;
+        rom[0x7FFF] = 0x56;
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+
+        assert_eq!(cartridge.read(0x0000), Ok(0x12));
+        assert_eq!(cartridge.read(0x0100), Ok(0x34));
+        assert_eq!(cartridge.read(0x7FFF), Ok(0x56));
+    }
+
+    /// An MBC1 image of `banks` banks, each starting with its own number
+    fn mbc1_rom(banks: usize) -> Box<[u8]> {
+        let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom[0x0147..0x014A].copy_from_slice(&[0x03, (banks / 2).trailing_zeros() as u8, 0x03]);
+        rom.into_boxed_slice()
+    }
+
+    #[test]
+    fn mbc1_banks_rom_and_ram() {
+        let mut cartridge = Cartridge::new(mbc1_rom(8)).unwrap();
+        assert_eq!(cartridge.read(0x4000), Ok(1));
+
+        cartridge.write(0x2000, 0x06);
+        assert_eq!(cartridge.read(0x4000), Ok(6));
+
+        // bank numbers past the end of the ROM wrap around
+        cartridge.write(0x2000, 0x0B);
+        assert_eq!(cartridge.read(0x4000), Ok(3));
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x6000, 0x01);
+        cartridge.write(0x4000, 0x03);
+        cartridge.write_ram(0xA000, 0x12);
+        assert_eq!(cartridge.read_ram(0xA000), 0x12);
+        assert_eq!(cartridge.ram[3 * 0x2000], 0x12);
+    }
+
+    #[test]
+    fn mbc2_brings_its_own_ram() {
+        let mut rom = vec![0x00; 4 * ROM_BANK_SIZE];
+        rom[0x0147..0x014A].copy_from_slice(&[0x06, 0x01, 0x00]);
+        let mut cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        assert!(cartridge.header().cartridge_type.battery);
+        assert_eq!(cartridge.ram.len(), 0x200);
+
+        cartridge.write_ram(0xA123, 0x07);
+        assert!(!cartridge.take_ram_written());
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write_ram(0xA123, 0x07);
+        assert_eq!(cartridge.read_ram(0xA123), 0xF7);
+        assert!(cartridge.take_ram_written());
+    }
+
+    #[test]
+    fn mbc3_saves_the_clock_after_the_ram() {
+        let mut rom = vec![0x00; 4 * ROM_BANK_SIZE];
+        rom[0x0147..0x014A].copy_from_slice(&[0x10, 0x01, 0x02]);
+        let mut cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        cartridge.set_rtc_clock(RtcClock::Emulated);
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x12);
+        cartridge.write(0x4000, 0x0A);
+        cartridge.write_ram(0xA000, 0x05);
+        let data = cartridge.save_data();
+        assert_eq!(data.len(), 0x2000 + 48);
+        assert_eq!(data[0x2000 + 8], 0x05);
+
+        let mut restored = Cartridge::new(cartridge.rom.clone()).unwrap();
+        restored.set_rtc_clock(RtcClock::Emulated);
+        restored.load_save_data(&data);
+        restored.write(0x0000, 0x0A);
+        restored.write(0x6000, 0x00);
+        restored.write(0x6000, 0x01);
+        restored.write(0x4000, 0x0A);
+        assert_eq!(restored.read_ram(0xA000), 0x05);
+        restored.write(0x4000, 0x00);
+        assert_eq!(restored.read_ram(0xA000), 0x12);
     }
 
-    pub fn read(&self, addr: u16) -> u8 {
-        let actual_addr = addr - 0x0100;
-        return self.rom[actual_addr as usize];
+    #[test]
+    fn mbc5_maps_eight_mebibytes() {
+        let mut rom = vec![0x00; 512 * ROM_BANK_SIZE];
+        rom[0x0147..0x014A].copy_from_slice(&[0x1E, 0x08, 0x04]);
+        rom[0x1FF * ROM_BANK_SIZE] = 0x42;
+        let mut cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        assert_eq!(cartridge.ram.len(), 0x20000);
+
+        cartridge.write(0x2000, 0xFF);
+        cartridge.write(0x3000, 0x01);
+        assert_eq!(cartridge.read(0x4000), Ok(0x42));
+    }
+
+    #[test]
+    fn mmm01_boots_the_menu_at_the_end() {
+        let mut rom = vec![0x00; 8 * ROM_BANK_SIZE];
+        let menu = &mut rom[6 * ROM_BANK_SIZE..];
+        menu[0x0000] = 0x42;
+        menu[header::LOGO_ADDR..header::LOGO_ADDR + 0x30].copy_from_slice(&header::NINTENDO_LOGO);
+        menu[0x0147..0x014A].copy_from_slice(&[0x0B, 0x02, 0x00]);
+        menu[0x014D] = header::header_checksum(menu);
+        let cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+
+        assert_eq!(cartridge.header().cartridge_type.mapper, Mapper::Mmm01);
+        assert_eq!(cartridge.read(0x0000), Ok(0x42));
+    }
+
+    #[test]
+    fn mbc7_keeps_its_eeprom_in_ram() {
+        let mut rom = vec![0x00; 4 * ROM_BANK_SIZE];
+        rom[0x0147..0x014A].copy_from_slice(&[0x22, 0x01, 0x00]);
+        let mut cartridge = Cartridge::new(rom.into_boxed_slice()).unwrap();
+        assert_eq!(cartridge.save_data().len(), 0x100);
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(0x4000, 0x40);
+        cartridge.set_tilt(0.0, 1.0);
+        cartridge.write_ram(0xA000, 0x55);
+        cartridge.write_ram(0xA010, 0xAA);
+        assert_eq!(cartridge.read_ram(0xA040), 0x40);
+        assert_eq!(cartridge.read_ram(0xA050), 0x82);
+    }
+
+    #[test]
+    fn truncated_image_faults() {
+        let rom = mbc1_rom(8);
+        let mut cartridge = Cartridge::new(rom[..0x10000].to_vec().into_boxed_slice()).unwrap();
+
+        cartridge.write(0x2000, 0x05);
+        assert!(cartridge.read(0x4000).is_err());
     }
 }
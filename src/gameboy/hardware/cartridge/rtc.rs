@@ -0,0 +1,337 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// T-cycles in a second at normal speed. The RTC has its own 32768 Hz
+/// crystal, so double speed mode doesn't make it run faster.
+const CYCLES_PER_SECOND: usize = 4_194_304;
+
+/// Size of the trailer BGB and VBA-M append to the RAM in a save file:
+/// the clock and latched registers as ten little-endian 32-bit words,
+/// followed by a 64-bit UNIX timestamp of when it was written
+pub const TRAILER_LEN: usize = 48;
+
+/// Older versions wrote the timestamp as 32 bits
+const SHORT_TRAILER_LEN: usize = 44;
+
+const DAY_HIGH: u8 = 0x01;
+const HALT: u8 = 0x40;
+const CARRY: u8 = 0x80;
+
+/// What moves the clock on
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RtcClock {
+    /// Emulated cycles, so the clock keeps pace with the game even when
+    /// the emulation runs slower or faster than real time, or is paused
+    Emulated,
+    /// The host's wall clock, as if the cartridge sat in a real console
+    Host,
+}
+
+impl FromStr for RtcClock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "emulated" => Ok(RtcClock::Emulated),
+            "host" => Ok(RtcClock::Host),
+            _ => Err(format!("unknown RTC clock {}", s)),
+        }
+    }
+}
+
+/// The MBC3 real time clock: seconds, minutes, hours and a 9-bit day
+/// counter with halt and carry flags. The CPU only sees a latched copy,
+/// so the registers can't change while they're being read.
+pub struct Rtc {
+    clock: RtcClock,
+    /// Seconds, minutes, hours, day counter low, day counter high
+    regs: [u8; 5],
+    latched: [u8; 5],
+    /// Cycles into the current second, for the emulated clock
+    cycles: usize,
+    /// Last time the host clock was caught up with, in UNIX seconds
+    synced: u64,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            clock: RtcClock::Host,
+            regs: [0; 5],
+            latched: [0; 5],
+            cycles: 0,
+            synced: now(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: RtcClock) {
+        self.sync();
+        self.clock = clock;
+    }
+
+    pub fn step(&mut self, cycles: usize) {
+        if self.clock != RtcClock::Emulated || self.halted() {
+            return;
+        }
+
+        self.cycles += cycles;
+        let seconds = self.cycles / CYCLES_PER_SECOND;
+        self.cycles %= CYCLES_PER_SECOND;
+        self.advance(seconds as u64);
+    }
+
+    /// Copies the running registers to the ones the CPU can read
+    pub fn latch(&mut self) {
+        self.sync();
+        self.latched = self.regs;
+    }
+
+    /// Reads RTC register $08-$0C
+    pub fn read(&self, reg: u8) -> u8 {
+        self.latched[(reg - 0x08) as usize]
+    }
+
+    /// Writes RTC register $08-$0C. Writing the seconds also restarts the
+    /// second in progress.
+    pub fn write(&mut self, reg: u8, val: u8) {
+        self.sync();
+
+        let i = (reg - 0x08) as usize;
+        let val = val & [0x3F, 0x3F, 0x1F, 0xFF, DAY_HIGH | HALT | CARRY][i];
+        self.regs[i] = val;
+        self.latched[i] = val;
+        if i == 0 {
+            self.cycles = 0;
+        }
+    }
+
+    /// The clock and latched registers in the BGB/VBA-M save trailer, as
+    /// of now
+    pub fn save(&mut self) -> [u8; TRAILER_LEN] {
+        self.sync();
+
+        let mut trailer = [0; TRAILER_LEN];
+        for (i, &reg) in self.regs.iter().chain(self.latched.iter()).enumerate() {
+            trailer[i * 4] = reg;
+        }
+        for (i, byte) in trailer[40..].iter_mut().enumerate() {
+            *byte = (self.synced >> (i * 8)) as u8;
+        }
+
+        trailer
+    }
+
+    /// Restores the clock from a save trailer. The console was off since
+    /// it was written, but the cartridge's clock wasn't, so it's moved on
+    /// by the time that passed. Returns false for anything that isn't a
+    /// trailer.
+    pub fn load(&mut self, trailer: &[u8]) -> bool {
+        if trailer.len() != TRAILER_LEN && trailer.len() != SHORT_TRAILER_LEN {
+            return false;
+        }
+
+        let word = |i: usize| trailer[i * 4] & [0x3F, 0x3F, 0x1F, 0xFF, DAY_HIGH | HALT | CARRY][i % 5];
+        for i in 0..5 {
+            self.regs[i] = word(i);
+            self.latched[i] = word(i + 5);
+        }
+
+        let saved = trailer[40..]
+            .iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | byte as u64);
+        let now = now();
+        if !self.halted() {
+            self.advance(now.saturating_sub(saved));
+        }
+        self.synced = now;
+
+        true
+    }
+
+    fn halted(&self) -> bool {
+        self.regs[4] & HALT != 0
+    }
+
+    /// Catches up with the host clock
+    fn sync(&mut self) {
+        let now = now();
+        if self.clock == RtcClock::Host && !self.halted() {
+            self.advance(now.saturating_sub(self.synced));
+        }
+        self.synced = now;
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        // registers written out of range count up to their 6 or 5-bit limit
+        // and wrap to zero without carrying, one second at a time
+        while seconds > 0 && (self.regs[0] >= 60 || self.regs[1] >= 60 || self.regs[2] >= 24) {
+            self.tick();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let days = (self.regs[4] as u64 & 0x01) << 8 | self.regs[3] as u64;
+        let total =
+            self.regs[0] as u64 + self.regs[1] as u64 * 60 + self.regs[2] as u64 * 3600 + days * 86400 + seconds;
+
+        let days = total / 86400;
+        self.regs[0] = (total % 60) as u8;
+        self.regs[1] = (total / 60 % 60) as u8;
+        self.regs[2] = (total / 3600 % 24) as u8;
+        self.regs[3] = days as u8;
+        self.regs[4] = self.regs[4] & !DAY_HIGH | (days >> 8) as u8 & DAY_HIGH;
+        if days > 0x1FF {
+            self.regs[4] |= CARRY;
+        }
+    }
+
+    fn tick(&mut self) {
+        self.regs[0] = (self.regs[0] + 1) & 0x3F;
+        if self.regs[0] != 60 {
+            return;
+        }
+        self.regs[0] = 0;
+
+        self.regs[1] = (self.regs[1] + 1) & 0x3F;
+        if self.regs[1] != 60 {
+            return;
+        }
+        self.regs[1] = 0;
+
+        self.regs[2] = (self.regs[2] + 1) & 0x1F;
+        if self.regs[2] != 24 {
+            return;
+        }
+        self.regs[2] = 0;
+
+        self.regs[3] = self.regs[3].wrapping_add(1);
+        if self.regs[3] == 0 {
+            if self.regs[4] & DAY_HIGH != 0 {
+                self.regs[4] = self.regs[4] & !DAY_HIGH | CARRY;
+            } else {
+                self.regs[4] |= DAY_HIGH;
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emulated() -> Rtc {
+        let mut rtc = Rtc::new();
+        rtc.set_clock(RtcClock::Emulated);
+        rtc
+    }
+
+    fn latched(rtc: &mut Rtc) -> [u8; 5] {
+        rtc.latch();
+        [rtc.read(0x08), rtc.read(0x09), rtc.read(0x0A), rtc.read(0x0B), rtc.read(0x0C)]
+    }
+
+    #[test]
+    fn counts_emulated_seconds() {
+        let mut rtc = emulated();
+        rtc.step(CYCLES_PER_SECOND - 1);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0]);
+
+        rtc.step(1);
+        assert_eq!(latched(&mut rtc), [1, 0, 0, 0, 0]);
+
+        rtc.step(CYCLES_PER_SECOND * 3661);
+        assert_eq!(latched(&mut rtc), [2, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn reads_stay_latched() {
+        let mut rtc = emulated();
+        rtc.step(CYCLES_PER_SECOND * 5);
+        rtc.latch();
+
+        rtc.step(CYCLES_PER_SECOND * 5);
+        assert_eq!(rtc.read(0x08), 5);
+        rtc.latch();
+        assert_eq!(rtc.read(0x08), 10);
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_carry() {
+        let mut rtc = emulated();
+        rtc.write(0x0A, 23);
+        rtc.write(0x09, 59);
+        rtc.write(0x08, 59);
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+
+        rtc.step(CYCLES_PER_SECOND);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0x00, CARRY]);
+
+        // the carry stays until cleared
+        rtc.step(CYCLES_PER_SECOND * 86400);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0x01, CARRY]);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = emulated();
+        rtc.write(0x0C, HALT);
+        rtc.step(CYCLES_PER_SECOND * 10);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, HALT]);
+    }
+
+    #[test]
+    fn out_of_range_values_wrap_without_carrying() {
+        let mut rtc = emulated();
+        rtc.write(0x08, 0x3F);
+        rtc.step(CYCLES_PER_SECOND);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0]);
+
+        rtc.write(0x0A, 0x1F);
+        rtc.step(CYCLES_PER_SECOND * 3600);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn trailer_round_trips() {
+        let mut rtc = emulated();
+        rtc.write(0x0C, HALT | DAY_HIGH);
+        rtc.write(0x08, 12);
+        rtc.write(0x0B, 0x34);
+        let trailer = rtc.save();
+        assert_eq!(&trailer[..8], &[12, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(trailer[16], HALT | DAY_HIGH);
+
+        let mut restored = emulated();
+        assert!(restored.load(&trailer));
+        assert_eq!(latched(&mut restored), [12, 0, 0, 0x34, HALT | DAY_HIGH]);
+        assert!(!restored.load(&trailer[..40]));
+    }
+
+    #[test]
+    fn loading_catches_up_with_the_time_away() {
+        let mut trailer = [0; TRAILER_LEN];
+        let saved = now() - 90_061;
+        for (i, byte) in trailer[40..].iter_mut().enumerate() {
+            *byte = (saved >> (i * 8)) as u8;
+        }
+
+        // the host clock may move on a second in between
+        let mut rtc = emulated();
+        assert!(rtc.load(&trailer));
+        let regs = latched(&mut rtc);
+        assert!(regs[0] == 1 || regs[0] == 2);
+        assert_eq!(regs[1..], [1, 1, 1, 0]);
+        assert!(rtc.load(&trailer[..SHORT_TRAILER_LEN]));
+    }
+}
@@ -0,0 +1,333 @@
+use super::registers::Flags;
+
+pub fn add(a: u8, b: u8, carry: bool) -> (u8, Flags) {
+    let carry_val = if carry { 1 } else { 0 };
+    let res = a.wrapping_add(b).wrapping_add(carry_val);
+
+    let flags = Flags::ZERO.self_or_empty(res == 0)
+        | Flags::HALF_CARRY.self_or_empty((a & 0xf) + (b & 0xf) + carry_val > 0xf)
+        | Flags::CARRY.self_or_empty((a as u16) + (b as u16) + (carry_val as u16) > 0xff);
+
+    (res, flags)
+}
+
+pub fn sub(a: u8, b: u8, carry: bool) -> (u8, Flags) {
+    let carry_val = if carry { 1 } else { 0 };
+    let res = a.wrapping_sub(b).wrapping_sub(carry_val);
+
+    let flags = Flags::ZERO.self_or_empty(res == 0)
+        | Flags::ADD_SUB
+        | Flags::HALF_CARRY.self_or_empty((a & 0xf) < (b & 0xf) + carry_val)
+        | Flags::CARRY.self_or_empty((a as u16) < (b as u16) + (carry_val as u16));
+
+    (res, flags)
+}
+
+pub fn and(a: u8, b: u8) -> (u8, Flags) {
+    let res = a & b;
+    (res, Flags::ZERO.self_or_empty(res == 0) | Flags::HALF_CARRY)
+}
+
+pub fn xor(a: u8, b: u8) -> (u8, Flags) {
+    let res = a ^ b;
+    (res, Flags::ZERO.self_or_empty(res == 0))
+}
+
+pub fn or(a: u8, b: u8) -> (u8, Flags) {
+    let res = a | b;
+    (res, Flags::ZERO.self_or_empty(res == 0))
+}
+
+/// 16-bit addition as done by ADD HL,rr: H and C come from bits 11 and 15,
+/// Z is left for the caller to preserve
+pub fn add16(a: u16, b: u16) -> (u16, Flags) {
+    let flags = Flags::HALF_CARRY.self_or_empty((a & 0x0fff) + (b & 0x0fff) > 0x0fff)
+        | Flags::CARRY.self_or_empty((a as u32) + (b as u32) > 0xffff);
+
+    (a.wrapping_add(b), flags)
+}
+
+/// Signed offset addition as done by ADD SP,e8 and LD HL,SP+e8: H and C come
+/// from the unsigned addition on the low byte, Z and N are always cleared
+pub fn add_offset(a: u16, offset: i8) -> (u16, Flags) {
+    let b = offset as u8 as u16;
+
+    let flags = Flags::HALF_CARRY.self_or_empty((a & 0x0f) + (b & 0x0f) > 0x0f)
+        | Flags::CARRY.self_or_empty((a & 0xff) + b > 0xff);
+
+    (a.wrapping_add(offset as i16 as u16), flags)
+}
+
+/// Adjusts A back to BCD after an ADD/ADC or SUB/SBC of two BCD values,
+/// using the N, H and C flags that operation left behind
+pub fn daa(a: u8, flags: Flags) -> (u8, Flags) {
+    let mut res = a;
+    let mut carry = flags.contains(Flags::CARRY);
+
+    if flags.contains(Flags::ADD_SUB) {
+        if carry {
+            res = res.wrapping_sub(0x60);
+        }
+        if flags.contains(Flags::HALF_CARRY) {
+            res = res.wrapping_sub(0x06);
+        }
+    } else {
+        if carry || res > 0x99 {
+            res = res.wrapping_add(0x60);
+            carry = true;
+        }
+        if flags.contains(Flags::HALF_CARRY) || res & 0x0f > 0x09 {
+            res = res.wrapping_add(0x06);
+        }
+    }
+
+    let flags = Flags::ZERO.self_or_empty(res == 0)
+        | (Flags::ADD_SUB & flags)
+        | Flags::CARRY.self_or_empty(carry);
+
+    (res, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a "ZNHC" flag pattern, with `-` marking a cleared flag
+    fn flags(pattern: &str) -> Flags {
+        pattern
+            .chars()
+            .zip(&[Flags::ZERO, Flags::ADD_SUB, Flags::HALF_CARRY, Flags::CARRY])
+            .filter(|&(c, _)| c != '-')
+            .fold(Flags::empty(), |acc, (_, &flag)| acc | flag)
+    }
+
+    fn check_carry_op(name: &str, op: fn(u8, u8, bool) -> (u8, Flags), vectors: &[(u8, u8, bool, u8, &str)]) {
+        for &(a, b, carry, res, pattern) in vectors {
+            assert_eq!(
+                op(a, b, carry),
+                (res, flags(pattern)),
+                "{} ${:02X},${:02X} carry={}",
+                name,
+                a,
+                b,
+                carry
+            );
+        }
+    }
+
+    fn check_logic_op(name: &str, op: fn(u8, u8) -> (u8, Flags), vectors: &[(u8, u8, u8, &str)]) {
+        for &(a, b, res, pattern) in vectors {
+            assert_eq!(op(a, b), (res, flags(pattern)), "{} ${:02X},${:02X}", name, a, b);
+        }
+    }
+
+    #[test]
+    fn add_vectors() {
+        check_carry_op(
+            "ADD",
+            add,
+            &[
+                (0x00, 0x00, false, 0x00, "Z---"),
+                (0x3C, 0x12, false, 0x4E, "----"),
+                (0x0F, 0x01, false, 0x10, "--H-"),
+                (0xF0, 0x10, false, 0x00, "Z--C"),
+                (0x3C, 0xFF, false, 0x3B, "--HC"),
+                (0x3A, 0xC6, false, 0x00, "Z-HC"),
+                (0xFF, 0x01, false, 0x00, "Z-HC"),
+                (0x80, 0x80, false, 0x00, "Z--C"),
+            ],
+        );
+    }
+
+    #[test]
+    fn adc_vectors() {
+        check_carry_op(
+            "ADC",
+            add,
+            &[
+                (0xE1, 0x0F, true, 0xF1, "--H-"),
+                (0xE1, 0x3B, true, 0x1D, "---C"),
+                (0xE1, 0x1E, true, 0x00, "Z-HC"),
+                (0x0F, 0x00, true, 0x10, "--H-"),
+                (0xFF, 0x00, true, 0x00, "Z-HC"),
+                (0x00, 0x00, true, 0x01, "----"),
+                (0x7F, 0x80, true, 0x00, "Z-HC"),
+            ],
+        );
+    }
+
+    #[test]
+    fn sub_vectors() {
+        check_carry_op(
+            "SUB",
+            sub,
+            &[
+                (0x3E, 0x3E, false, 0x00, "ZN--"),
+                (0x3E, 0x0F, false, 0x2F, "-NH-"),
+                (0x3E, 0x40, false, 0xFE, "-N-C"),
+                (0x00, 0x01, false, 0xFF, "-NHC"),
+                (0x10, 0x01, false, 0x0F, "-NH-"),
+                (0x80, 0x01, false, 0x7F, "-NH-"),
+            ],
+        );
+    }
+
+    #[test]
+    fn sbc_vectors() {
+        check_carry_op(
+            "SBC",
+            sub,
+            &[
+                (0x3B, 0x2A, true, 0x10, "-N--"),
+                (0x3B, 0x3A, true, 0x00, "ZN--"),
+                (0x3B, 0x4F, true, 0xEB, "-NHC"),
+                (0x00, 0xFF, true, 0x00, "ZNHC"),
+                (0x10, 0x0F, true, 0x00, "ZNH-"),
+                (0x00, 0x00, true, 0xFF, "-NHC"),
+            ],
+        );
+    }
+
+    #[test]
+    fn and_vectors() {
+        check_logic_op(
+            "AND",
+            and,
+            &[
+                (0x5A, 0x3F, 0x1A, "--H-"),
+                (0x5A, 0x38, 0x18, "--H-"),
+                (0x5A, 0x00, 0x00, "Z-H-"),
+                (0xFF, 0xFF, 0xFF, "--H-"),
+            ],
+        );
+    }
+
+    #[test]
+    fn xor_vectors() {
+        check_logic_op(
+            "XOR",
+            xor,
+            &[
+                (0xFF, 0xFF, 0x00, "Z---"),
+                (0xFF, 0x0F, 0xF0, "----"),
+                (0xFF, 0x8A, 0x75, "----"),
+                (0x00, 0x00, 0x00, "Z---"),
+            ],
+        );
+    }
+
+    #[test]
+    fn or_vectors() {
+        check_logic_op(
+            "OR",
+            or,
+            &[
+                (0x5A, 0x5A, 0x5A, "----"),
+                (0x5A, 0x03, 0x5B, "----"),
+                (0x5A, 0x0F, 0x5F, "----"),
+                (0x00, 0x00, 0x00, "Z---"),
+            ],
+        );
+    }
+
+    #[test]
+    fn add16_vectors() {
+        for &(a, b, res, pattern) in &[
+            (0x0000u16, 0x0000u16, 0x0000u16, "----"),
+            (0x8A23, 0x0605, 0x9028, "--H-"),
+            (0x8A23, 0x8A23, 0x1446, "--HC"),
+            (0xFFFF, 0x0001, 0x0000, "--HC"),
+            (0xF000, 0x1000, 0x0000, "---C"),
+            (0x00FF, 0x0001, 0x0100, "----"),
+        ] {
+            assert_eq!(add16(a, b), (res, flags(pattern)), "ADD ${:04X},${:04X}", a, b);
+        }
+    }
+
+    #[test]
+    fn add_offset_vectors() {
+        for &(a, offset, res, pattern) in &[
+            (0xFFF8u16, 0x02i8, 0xFFFAu16, "----"),
+            (0xFFF8, 0x08, 0x0000, "--HC"),
+            (0x000F, 0x01, 0x0010, "--H-"),
+            (0x00F0, 0x10, 0x0100, "---C"),
+            // negative offsets still flag on the unsigned low byte addition
+            (0x0000, -1, 0xFFFF, "----"),
+            (0x0001, -1, 0x0000, "--HC"),
+            (0xFFFF, -1, 0xFFFE, "--HC"),
+            (0x1000, -128, 0x0F80, "----"),
+        ] {
+            assert_eq!(add_offset(a, offset), (res, flags(pattern)), "${:04X}{:+}", a, offset);
+        }
+    }
+
+    /// Reference DAA that adjusts the low digit first in 16 bits, the way
+    /// several hardware-verified emulators write it
+    fn daa_reference(a: u8, flags: Flags) -> (u8, Flags) {
+        let mut res = a as u16;
+
+        if flags.contains(Flags::ADD_SUB) {
+            if flags.contains(Flags::HALF_CARRY) {
+                res = res.wrapping_sub(0x06) & 0xff;
+            }
+            if flags.contains(Flags::CARRY) {
+                res = res.wrapping_sub(0x60);
+            }
+        } else {
+            if flags.contains(Flags::HALF_CARRY) || res & 0x0f > 0x09 {
+                res += 0x06;
+            }
+            if flags.contains(Flags::CARRY) || res > 0x9f {
+                res += 0x60;
+            }
+        }
+
+        let carry = flags.contains(Flags::CARRY) || res & 0x100 != 0;
+        let res = res as u8;
+
+        (
+            res,
+            Flags::ZERO.self_or_empty(res == 0)
+                | (Flags::ADD_SUB & flags)
+                | Flags::CARRY.self_or_empty(carry),
+        )
+    }
+
+    #[test]
+    fn daa_all_inputs() {
+        for a in 0..=0xffu8 {
+            for bits in 0..16u8 {
+                let flags = Flags::from_bits_truncate(bits << 4);
+                assert_eq!(daa(a, flags), daa_reference(a, flags), "DAA A=${:02X} F={:?}", a, flags);
+            }
+        }
+    }
+
+    #[test]
+    fn daa_yields_bcd_sums_and_differences() {
+        let bcd = |n: u8| ((n / 10) << 4) | (n % 10);
+
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                for &carry in &[false, true] {
+                    let c = carry as u8;
+
+                    let (sum, flags) = add(bcd(x), bcd(y), carry);
+                    let (res, flags) = daa(sum, flags);
+                    let total = x as u16 + y as u16 + c as u16;
+                    assert_eq!(res, bcd((total % 100) as u8), "{} + {} + {}", x, y, c);
+                    assert_eq!(flags.contains(Flags::CARRY), total >= 100);
+                    assert_eq!(flags.contains(Flags::ZERO), res == 0);
+
+                    let (diff, flags) = sub(bcd(x), bcd(y), carry);
+                    let (res, flags) = daa(diff, flags);
+                    let borrow = (x as i16) - (y as i16) - (c as i16) < 0;
+                    let expected = ((x as i16) - (y as i16) - (c as i16) + 100) % 100;
+                    assert_eq!(res, bcd(expected as u8), "{} - {} - {}", x, y, c);
+                    assert_eq!(flags.contains(Flags::CARRY), borrow);
+                    assert!(flags.contains(Flags::ADD_SUB));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Decoding and rendering of instructions straight from memory, without
+//! going through (or disturbing) the CPU.
+
+use super::instructions::Instruction;
+use super::opcodes::{Mnemonic, Opcode, Operand, CB_OPCODES, OPCODES};
+use super::instruction;
+use super::super::bus::MemoryBus;
+
+/// Decodes the instruction at `addr`, returning it along with its length
+pub fn disassemble<B: MemoryBus>(bus: &B, addr: u16) -> (Instruction, usize) {
+    let (opcode, spec, imm) = fetch(bus, addr);
+    (instruction(opcode, spec, imm), spec.byte_length)
+}
+
+/// Renders the instruction at `addr` in RGBDS syntax, returning the text and
+/// the instruction length. Relative jumps show their absolute target.
+pub fn render<B: MemoryBus>(bus: &B, addr: u16) -> (String, usize) {
+    render_with(bus, addr, |_| None)
+}
+
+/// Like `render`, but jump and call targets for which `label` returns a
+/// name are shown by that name
+pub fn render_with<B, F>(bus: &B, addr: u16, label: F) -> (String, usize)
+    where
+        B: MemoryBus,
+        F: Fn(u16) -> Option<String>,
+{
+    let (opcode, spec, imm) = fetch(bus, addr);
+    let next = addr.wrapping_add(spec.byte_length as u16);
+
+    let text = match spec.mnemonic {
+        Mnemonic::Illegal => format!("db ${:02X}", opcode),
+        mnemonic if spec.operands.is_empty() => mnemonic.to_string(),
+        mnemonic => {
+            let operands: Vec<String> = spec.operands
+                .iter()
+                .map(|&operand| render_operand(mnemonic, operand, imm, next, &label))
+                .collect();
+
+            format!("{} {}", mnemonic, operands.join(", "))
+        }
+    };
+
+    (text, spec.byte_length)
+}
+
+/// How the instruction at `addr` affects Z, N, H and C, as the opcode
+/// table puts it: `0` or `1` when forced, `-` when left alone, the flag's
+/// letter when it depends on the result
+pub fn flags<B: MemoryBus>(bus: &B, addr: u16) -> &'static str {
+    fetch(bus, addr).1.flags
+}
+
+fn fetch<B: MemoryBus>(bus: &B, addr: u16) -> (u8, &'static Opcode, u16) {
+    // unreadable bytes show up as open bus rather than failing the listing
+    let read = |offset: u16| bus.read(addr.wrapping_add(offset)).unwrap_or(0xFF);
+
+    let opcode = read(0);
+    let spec = &OPCODES[opcode as usize];
+
+    if spec.mnemonic == Mnemonic::Prefix {
+        let opcode = read(1);
+        return (opcode, &CB_OPCODES[opcode as usize], 0);
+    }
+
+    let imm = match spec.immediate_len() {
+        0 => 0,
+        1 => read(1) as u16,
+        _ => (read(2) as u16) << 8 | read(1) as u16,
+    };
+
+    (opcode, spec, imm)
+}
+
+fn render_operand<F>(mnemonic: Mnemonic, operand: Operand, imm: u16, next: u16, label: &F) -> String
+    where
+        F: Fn(u16) -> Option<String>,
+{
+    let target = |addr: u16| label(addr).unwrap_or_else(|| format!("${:04X}", addr));
+
+    match operand {
+        Operand::Reg8(reg) => format!("{:?}", reg).to_lowercase(),
+        Operand::Reg16(reg) => format!("{:?}", reg).to_lowercase(),
+        Operand::Ind(reg) => format!("[{:?}]", reg).to_lowercase(),
+        Operand::IndInc => "[hl+]".into(),
+        Operand::IndDec => "[hl-]".into(),
+        Operand::PagedC => "[$FF00+c]".into(),
+        Operand::PagedA8 => format!("[$FF{:02X}]", imm),
+        Operand::IndA16 => format!("[${:04X}]", imm),
+        Operand::D8 => format!("${:02X}", imm),
+        Operand::D16 => format!("${:04X}", imm),
+        Operand::A16 => target(imm),
+        Operand::R8 if mnemonic == Mnemonic::Jr => target(next.wrapping_add(imm as u8 as i8 as u16)),
+        Operand::R8 => signed(imm as u8 as i8),
+        Operand::SPOffset => match imm as u8 as i8 {
+            offset if offset < 0 => format!("sp{}", signed(offset)),
+            offset => format!("sp+{}", signed(offset)),
+        },
+        Operand::Cond(cond) => format!("{:?}", cond).to_lowercase(),
+        Operand::BitIndex(bit) => bit.to_string(),
+        Operand::Vector(addr) => format!("${:02X}", addr),
+    }
+}
+
+fn signed(val: i8) -> String {
+    if val < 0 {
+        format!("-${:02X}", -(val as i16))
+    } else {
+        format!("${:02X}", val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::error::Result;
+    use super::*;
+
+    struct Rom(Vec<u8>);
+
+    impl MemoryBus for Rom {
+        fn read(&self, addr: u16) -> Result<u8> {
+            Ok(self.0.get(addr as usize).cloned().unwrap_or(0))
+        }
+
+        fn write(&mut self, _: u16, _: u8) -> Result<()> {
+            panic!("the disassembler must not write")
+        }
+    }
+
+    fn listing(program: &[u8], addr: u16) -> Vec<String> {
+        let rom = Rom(program.to_vec());
+        let mut addr = addr;
+        let mut lines = Vec::new();
+
+        while (addr as usize) < program.len() {
+            let (text, len) = render(&rom, addr);
+            lines.push(text);
+            addr += len as u16;
+        }
+
+        lines
+    }
+
+    #[test]
+    fn renders_rgbds_syntax() {
+        let program = [
+            0x00, // nop
+            0x3E, 0x12, // ld a, $12
+            0x21, 0x34, 0x12, // ld hl, $1234
+            0x22, // ld [hl+], a
+            0xEA, 0x00, 0xC0, // ld [$C000], a
+            0xE0, 0x40, // ldh [$FF40], a
+            0xF2, // ld a, [$FF00+c]
+            0x08, 0x00, 0xD0, // ld [$D000], sp
+            0xF8, 0xFE, // ld hl, sp-$02
+            0xE8, 0x05, // add sp, $05
+            0x20, 0xFE, // jr nz, $0014 (itself)
+            0xCD, 0x00, 0x40, // call $4000
+            0xCB, 0x7E, // bit 7, [hl]
+            0xDF, // rst $18
+            0x10, 0x00, // stop
+            0xD3, // db $D3
+        ];
+
+        assert_eq!(
+            listing(&program, 0),
+            vec![
+                "nop",
+                "ld a, $12",
+                "ld hl, $1234",
+                "ld [hl+], a",
+                "ld [$C000], a",
+                "ldh [$FF40], a",
+                "ld a, [$FF00+c]",
+                "ld [$D000], sp",
+                "ld hl, sp-$02",
+                "add sp, $05",
+                "jr nz, $0014",
+                "call $4000",
+                "bit 7, [hl]",
+                "rst $18",
+                "stop",
+                "db $D3",
+            ]
+        );
+    }
+
+    #[test]
+    fn targets_use_labels() {
+        // JR $0004 ; CALL $1234 ; JP $0000
+        let rom = Rom(vec![0x18, 0x02, 0xCD, 0x34, 0x12, 0xC3, 0x00, 0x00]);
+        let label = |addr: u16| match addr {
+            0x0004 => Some("Loop".to_string()),
+            0x1234 => Some("Func".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(render_with(&rom, 0, label).0, "jr Loop");
+        assert_eq!(render_with(&rom, 2, label).0, "call Func");
+        assert_eq!(render_with(&rom, 5, label).0, "jp $0000");
+    }
+
+    #[test]
+    fn disassemble_matches_decoder() {
+        // CALL Z,$1234
+        let rom = Rom(vec![0x00, 0xCC, 0x34, 0x12]);
+        let (instr, len) = disassemble(&rom, 1);
+
+        assert_eq!(len, 3);
+        match instr {
+            Instruction::CallOn(info, _, addr) => {
+                assert_eq!(info.opcode, 0xCC);
+                assert_eq!(addr, 0x1234);
+            }
+            _ => panic!("expected CALL Z"),
+        }
+
+        // SWAP A
+        let rom = Rom(vec![0xCB, 0x37]);
+        let (instr, len) = disassemble(&rom, 0);
+
+        assert_eq!(len, 2);
+        assert_eq!(instr.info().cycle_duration, 8);
+    }
+
+    #[test]
+    fn flags_come_from_the_table() {
+        // XOR A ; SWAP A ; LD A,B
+        let rom = Rom(vec![0xAF, 0xCB, 0x37, 0x78]);
+
+        assert_eq!(flags(&rom, 0), "Z000");
+        assert_eq!(flags(&rom, 1), "Z000");
+        assert_eq!(flags(&rom, 3), "----");
+    }
+}
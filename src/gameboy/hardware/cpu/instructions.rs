@@ -1,28 +1,37 @@
-use std::fmt;
-
 use super::ops::Ops;
 use super::registers::{Flags, Reg16, Reg8};
 use super::super::bus::MemoryBus;
+use super::super::error::Result;
+use super::super::interrupts::Interrupt;
 
 pub trait InstructionDecoding {
-    fn decode<B: MemoryBus>(&mut self, opcode: u8, b: &mut B) -> Instruction;
-    fn decode_cb<B: MemoryBus>(&mut self, opcode: u8, b: &mut B) -> Instruction;
+    fn decode<B: MemoryBus>(&mut self, opcode: u8, b: &mut B) -> Result<Instruction>;
+    fn decode_cb<B: MemoryBus>(&mut self, opcode: u8, b: &mut B) -> Result<Instruction>;
 }
 
 #[derive(Copy, Clone, Default)]
 pub struct Info {
     pub opcode: u8,
     pub byte_length: usize,
+    /// Cycles taken when a conditional branch is not taken, or the only
+    /// cost for every other instruction
     pub cycle_duration: usize,
+    /// Extra cycles a conditional branch costs when it is taken
+    pub branch_penalty: usize,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum Src {
     PagedA8(u8),
+    PagedReg8(Reg8),
     D8(u8),
     D16(u16),
     Reg8(Reg8),
     Reg16(Reg16),
+    Ind(Reg16),
+    Reg16Inc(Reg16),
+    Reg16Dec(Reg16),
+    Addr(u16),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -31,15 +40,26 @@ pub enum Dst {
     Reg8(Reg8),
     PagedReg8(Reg8),
     Reg16(Reg16),
+    Ind(Reg16),
     Reg16Inc(Reg16),
     Reg16Dec(Reg16),
     Addr(u16),
+    Addr16(u16),
+}
+
+/// An 8-bit operand that is both read and written by the same instruction
+#[derive(Copy, Clone, Debug)]
+pub enum Loc8 {
+    Reg8(Reg8),
+    IndHL,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub enum JumpCondition {
     NZ,
     Z,
+    NC,
+    C,
 }
 
 impl JumpCondition {
@@ -49,116 +69,204 @@ impl JumpCondition {
         match *self {
             NZ => !flags.contains(Flags::ZERO),
             Z => flags.contains(Flags::ZERO),
+            NC => !flags.contains(Flags::CARRY),
+            C => flags.contains(Flags::CARRY),
         }
     }
 }
 
 pub enum Instruction {
     Nop(Info),
-    Bit(Info, usize, Reg8),
-    Dec(Info, Reg8),
-    Inc(Info, Reg8),
+    Stop(Info),
+    Halt(Info),
+    Lock(Info),
+    DisableInterrupts(Info),
+    EnableInterrupts(Info),
+    ServiceInterrupt(Info, Interrupt),
+    Bit(Info, usize, Loc8),
+    Reset(Info, usize, Loc8),
+    Set(Info, usize, Loc8),
+    Dec(Info, Loc8),
+    Inc(Info, Loc8),
     Load(Info, Dst, Src),
-    Xor(Info, Reg8),
+    LoadHLSP(Info, i8),
+    Add(Info, Src),
+    AddCarry(Info, Src),
+    Sub(Info, Src),
+    SubCarry(Info, Src),
+    And(Info, Src),
+    Xor(Info, Src),
+    Or(Info, Src),
+    Compare(Info, Src),
+    AddHL(Info, Reg16),
+    AddSP(Info, i8),
     Call(Info, u16),
+    CallOn(Info, JumpCondition, u16),
     JumpOn(Info, JumpCondition, i8),
     Jump(Info, i8),
-    Ret(Info, u16),
+    JumpAbs(Info, u16),
+    JumpAbsOn(Info, JumpCondition, u16),
+    JumpHL(Info),
+    Ret(Info),
+    RetOn(Info, JumpCondition),
+    RetInterrupt(Info),
+    Restart(Info, u8),
     Push16(Info, Reg16),
     Pop16(Info, Reg16),
-    RotateLeft(Info, Reg8, bool),
+    RotateLeft(Info, Loc8, bool),
     RotateLeftAkku(Info, bool),
+    RotateLeftCircular(Info, Loc8, bool),
+    RotateRight(Info, Loc8, bool),
+    RotateRightCircular(Info, Loc8, bool),
+    ShiftLeftArithmetic(Info, Loc8),
+    ShiftRightArithmetic(Info, Loc8),
+    ShiftRightLogical(Info, Loc8),
+    Swap(Info, Loc8),
+    RotateLeftCircularAkku(Info),
+    RotateRightAkku(Info),
+    RotateRightCircularAkku(Info),
+    DecimalAdjust(Info),
+    Complement(Info),
+    SetCarry(Info),
+    ComplementCarry(Info),
     Inc16(Info, Reg16),
-    Compare(Info, u8),
-
-    PrefixCB,
+    Dec16(Info, Reg16),
 }
 
 impl Instruction {
-    pub fn execute<O: Ops>(self, ops: O) -> Self {
+    /// Executes the instruction and returns the T-cycles it took, which for
+    /// conditional branches depends on whether the branch was taken
+    pub fn execute<O: Ops>(self, ops: O) -> Result<usize> {
         use self::Instruction::*;
 
-        match self {
-            Bit(_, bit, reg) => ops.bit(bit, reg),
-            Dec(_, reg) => ops.dec(reg),
-            Inc(_, reg) => ops.inc(reg),
-            Load(_, addr, reg) => ops.load(addr, reg),
-            Xor(_, reg) => ops.xor(reg),
-            Call(_, addr) => ops.call(addr),
-            JumpOn(_, cond, offset) => ops.jr_c(cond, offset),
-            Jump(_, offset) => ops.jr(offset),
-            Ret(_, addr) => ops.ret(addr),
-            Push16(_, reg) => ops.push16(reg),
-            Pop16(_, reg) => ops.pop16(reg),
-            RotateLeft(_, reg, set_zero) => ops.rl(reg, set_zero),
-            RotateLeftAkku(_, set_zero) => ops.rl(Reg8::A, set_zero),
-            Inc16(_, reg) => ops.inc16(reg),
-            Compare(_, val) => ops.sub(val),
-
-            PrefixCB => return ops.prefix_cb(),
-
-            Nop(_) => ops.nop(),
-        }
-
-        self
-    }
-}
+        let info = self.info();
+        let mut taken = false;
 
-impl fmt::Display for Src {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::Src::*;
+        match self {
+            Stop(_) => ops.stop()?,
+            Halt(_) => ops.halt()?,
+            Lock(_) => ops.lock()?,
+            DisableInterrupts(_) => ops.di()?,
+            EnableInterrupts(_) => ops.ei()?,
+            ServiceInterrupt(_, irq) => ops.isr(irq)?,
+            Bit(_, bit, loc) => ops.bit(bit, loc)?,
+            Reset(_, bit, loc) => ops.res(bit, loc)?,
+            Set(_, bit, loc) => ops.set(bit, loc)?,
+            Dec(_, loc) => ops.dec(loc)?,
+            Inc(_, loc) => ops.inc(loc)?,
+            Load(_, addr, reg) => ops.load(addr, reg)?,
+            LoadHLSP(_, offset) => ops.load_hl_sp(offset)?,
+            Add(_, src) => ops.add(src)?,
+            AddCarry(_, src) => ops.adc(src)?,
+            Sub(_, src) => ops.sub(src)?,
+            SubCarry(_, src) => ops.sbc(src)?,
+            And(_, src) => ops.and(src)?,
+            Xor(_, src) => ops.xor(src)?,
+            Or(_, src) => ops.or(src)?,
+            Compare(_, src) => ops.cp(src)?,
+            AddHL(_, reg) => ops.add_hl(reg)?,
+            AddSP(_, offset) => ops.add_sp(offset)?,
+            Call(_, addr) => ops.call(addr)?,
+            CallOn(_, cond, addr) => taken = ops.call_c(cond, addr)?,
+            JumpOn(_, cond, offset) => taken = ops.jr_c(cond, offset)?,
+            Jump(_, offset) => ops.jr(offset)?,
+            JumpAbs(_, addr) => ops.jp(addr)?,
+            JumpAbsOn(_, cond, addr) => taken = ops.jp_c(cond, addr)?,
+            JumpHL(_) => ops.jp_hl()?,
+            Ret(_) => ops.ret()?,
+            RetOn(_, cond) => taken = ops.ret_c(cond)?,
+            RetInterrupt(_) => ops.reti()?,
+            Restart(_, addr) => ops.rst(addr)?,
+            Push16(_, reg) => ops.push16(reg)?,
+            Pop16(_, reg) => ops.pop16(reg)?,
+            RotateLeft(_, loc, set_zero) => ops.rl(loc, set_zero)?,
+            RotateLeftAkku(_, set_zero) => ops.rl(Loc8::Reg8(Reg8::A), set_zero)?,
+            RotateLeftCircular(_, loc, set_zero) => ops.rlc(loc, set_zero)?,
+            RotateLeftCircularAkku(_) => ops.rlc(Loc8::Reg8(Reg8::A), false)?,
+            RotateRight(_, loc, set_zero) => ops.rr(loc, set_zero)?,
+            RotateRightAkku(_) => ops.rr(Loc8::Reg8(Reg8::A), false)?,
+            RotateRightCircular(_, loc, set_zero) => ops.rrc(loc, set_zero)?,
+            RotateRightCircularAkku(_) => ops.rrc(Loc8::Reg8(Reg8::A), false)?,
+            ShiftLeftArithmetic(_, loc) => ops.sla(loc)?,
+            ShiftRightArithmetic(_, loc) => ops.sra(loc)?,
+            ShiftRightLogical(_, loc) => ops.srl(loc)?,
+            Swap(_, loc) => ops.swap(loc)?,
+            DecimalAdjust(_) => ops.daa()?,
+            Complement(_) => ops.cpl()?,
+            SetCarry(_) => ops.scf()?,
+            ComplementCarry(_) => ops.ccf()?,
+            Inc16(_, reg) => ops.inc16(reg)?,
+            Dec16(_, reg) => ops.dec16(reg)?,
 
-        match *self {
-            PagedA8(val) => write!(f, "($FF00+${:#04X})", val),
-            D8(val) => write!(f, "${:#04X}", val),
-            D16(val) => write!(f, "${:#04X}", val),
-            Reg8(reg) => write!(f, "{:?}", reg),
-            Reg16(reg) => write!(f, "({:?})", reg),
+            Nop(_) => ops.nop()?,
         }
-    }
-}
-
-impl fmt::Display for Dst {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::Dst::*;
 
-        match *self {
-            A8(val) => write!(f, "($FF00+${:#04X})", val),
-            Reg8(reg) => write!(f, "{:?}", reg),
-            PagedReg8(reg) => write!(f, "($FF00+{:?})", reg),
-            Reg16(reg) => write!(f, "{:?}", reg),
-            Reg16Inc(reg) => write!(f, "({:?}+)", reg),
-            Reg16Dec(reg) => write!(f, "({:?}-)", reg),
-            Addr(addr) => write!(f, "(${:#04X})", addr),
+        if taken {
+            Ok(info.cycle_duration + info.branch_penalty)
+        } else {
+            Ok(info.cycle_duration)
         }
     }
-}
 
-impl fmt::Display for Instruction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    pub fn info(&self) -> Info {
         use self::Instruction::*;
 
         match *self {
-            Nop(info) => write!(f, "[{:02X}] -> NOP", info.opcode),
-            Bit(info, bit, reg) => write!(f, "[{:02X}] -> BIT {:?},{:?}", info.opcode, bit, reg),
-            Dec(info, reg) => write!(f, "[{:02X}] -> DEC {:?}", info.opcode, reg),
-            Inc(info, reg) => write!(f, "[{:02X}] -> INC {:?}", info.opcode, reg),
-            Inc16(info, reg) => write!(f, "[{:02X}] -> INC {:?}", info.opcode, reg),
-            Compare(info, val) => write!(f, "[{:02X}] -> CP ${:#04X}", info.opcode, val),
-            Load(info, dst, src) => write!(f, "[{:02X}] -> LD {:},{:}", info.opcode, dst, src),
-            Xor(info, reg) => write!(f, "[{:02X}] -> XOR {:?}", info.opcode, reg),
-            Call(info, addr) => write!(f, "[{:02X}] -> CALL ${:#06X}", info.opcode, addr),
-            JumpOn(info, cd, addr) => {
-                write!(f, "[{:02X}] -> JR {:?},${:#04X}", info.opcode, cd, addr)
-            }
-            Jump(info, addr) => write!(f, "[{:02X}] -> JR ${:#04X}", info.opcode, addr),
-            Ret(info, addr) => write!(f, "[{:02X}] -> RET ${:#06X}", info.opcode, addr),
-            Push16(info, reg) => write!(f, "[{:02X}] -> PUSH {:?}", info.opcode, reg),
-            Pop16(info, reg) => write!(f, "[{:02X}] -> POP {:?}", info.opcode, reg),
-            RotateLeft(info, reg, _) => write!(f, "[{:02X}] -> RL {:?}", info.opcode, reg),
-            RotateLeftAkku(info, _) => write!(f, "[{:02X}] -> RLA", info.opcode),
-
-            PrefixCB => Ok(()),
+            Nop(info)
+            | Stop(info)
+            | Halt(info)
+            | Lock(info)
+            | DisableInterrupts(info)
+            | EnableInterrupts(info)
+            | ServiceInterrupt(info, _)
+            | Bit(info, ..)
+            | Reset(info, ..)
+            | Set(info, ..)
+            | Dec(info, _)
+            | Inc(info, _)
+            | Load(info, ..)
+            | LoadHLSP(info, _)
+            | Add(info, _)
+            | AddCarry(info, _)
+            | Sub(info, _)
+            | SubCarry(info, _)
+            | And(info, _)
+            | Xor(info, _)
+            | Or(info, _)
+            | Compare(info, _)
+            | AddHL(info, _)
+            | AddSP(info, _)
+            | Call(info, _)
+            | CallOn(info, ..)
+            | JumpOn(info, ..)
+            | Jump(info, _)
+            | JumpAbs(info, _)
+            | JumpAbsOn(info, ..)
+            | JumpHL(info)
+            | Ret(info)
+            | RetOn(info, _)
+            | RetInterrupt(info)
+            | Restart(info, _)
+            | Push16(info, _)
+            | Pop16(info, _)
+            | RotateLeft(info, ..)
+            | RotateLeftAkku(info, _)
+            | RotateLeftCircular(info, ..)
+            | RotateRight(info, ..)
+            | RotateRightCircular(info, ..)
+            | ShiftLeftArithmetic(info, _)
+            | ShiftRightArithmetic(info, _)
+            | ShiftRightLogical(info, _)
+            | Swap(info, _)
+            | RotateLeftCircularAkku(info)
+            | RotateRightAkku(info)
+            | RotateRightCircularAkku(info)
+            | DecimalAdjust(info)
+            | Complement(info)
+            | SetCarry(info)
+            | ComplementCarry(info)
+            | Inc16(info, _)
+            | Dec16(info, _) => info,
         }
     }
 }
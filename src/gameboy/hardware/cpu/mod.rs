@@ -1,436 +1,451 @@
+use log::Level;
+
 use super::bus::MemoryBus;
+use super::error::{EmuError, Result};
+use super::interrupts::{Interrupt, IE_ADDR, IF_ADDR};
 
 use self::instructions::*;
+use self::opcodes::{Mnemonic, Opcode, Operand, CB_OPCODES, OPCODES};
 use self::ops::Ops;
 use self::registers::{Flags, Reg16, Reg8, Registers};
 
-mod instructions;
+mod alu;
+pub mod disassembler;
+pub mod instructions;
+mod opcodes;
 mod ops;
 pub mod registers;
 
+/// How the CPU hands elapsed time to the rest of the hardware
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// Tick the bus once per instruction with its whole cost. Faster, but
+    /// every access observes the hardware as it was at the instruction start.
+    Batch,
+    /// Tick the bus by one M-cycle before every memory access, so each
+    /// access lands on the M-cycle it takes on real hardware.
+    MCycle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Running,
+    Halted,
+    Stopped,
+    Locked,
+}
+
 pub struct LR35902 {
     pub registers: Registers,
+    state: State,
+    ime: bool,
+    ei_pending: bool,
+    halt_bug: bool,
+    timing: Timing,
+    /// T-cycles already ticked on the bus during the current step
+    ticked: usize,
 }
 
 impl LR35902 {
     pub fn new() -> Self {
         Self {
             registers: Default::default(),
+            state: State::Running,
+            ime: false,
+            ei_pending: false,
+            halt_bug: false,
+            timing: Timing::Batch,
+            ticked: 0,
         }
     }
 
-    pub fn step<B: MemoryBus>(&mut self, bus: &mut B) -> Instruction {
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing
+    }
+
+    /// Runs a single instruction (or interrupt dispatch) and returns the
+    /// number of T-cycles it took. A halted or stopped CPU idles for 4 cycles.
+    /// By the time this returns the bus has been ticked by exactly that amount.
+    ///
+    /// Faults are returned instead of aborting: an illegal opcode leaves the
+    /// CPU locked on it, a failed access leaves the instruction half done.
+    pub fn step<B: MemoryBus>(&mut self, bus: &mut B) -> Result<usize> {
+        self.ticked = 0;
+
+        let cycles = self.run(bus)?;
+        self.charge(bus, cycles);
+
+        Ok(cycles)
+    }
+
+    /// T-cycles the bus was ticked by during the last step: what `step`
+    /// returned, or as far as a faulting instruction got
+    pub fn ticked(&self) -> usize {
+        self.ticked
+    }
+
+    /// Ticks the bus by whatever of `cycles` wasn't spent on memory accesses
+    fn charge<B: MemoryBus>(&mut self, bus: &mut B, cycles: usize) {
+        bus.tick(cycles - self.ticked);
+        self.ticked = cycles;
+    }
+
+    fn run<B: MemoryBus>(&mut self, bus: &mut B) -> Result<usize> {
+        let pending = self.pending_interrupt(bus)?;
+
+        match self.state {
+            // any enabled request wakes up a halted CPU, whether IME is set or not
+            State::Halted if pending.is_none() => return Ok(4),
+            State::Stopped if !self.joypad_pressed(bus)? => return Ok(4),
+            State::Halted | State::Stopped => self.state = State::Running,
+            State::Running | State::Locked => {}
+        }
+
+        if self.ime && self.state != State::Locked {
+            if let Some(irq) = pending {
+                // with the HALT bug armed, the interrupt returns to the HALT itself
+                if self.halt_bug {
+                    let pc = self.registers.read16(Reg16::PC);
+                    self.registers.write16(Reg16::PC, pc.wrapping_sub(1));
+                    self.halt_bug = false;
+                }
+
+                let info = Info {
+                    opcode: 0x00,
+                    byte_length: 0,
+                    cycle_duration: 20,
+                    branch_penalty: 0,
+                };
+
+                return Instruction::ServiceInterrupt(info, irq).execute((self, bus));
+            }
+        }
+
+        // EI takes effect only once the instruction following it is done
+        let enable_ime = self.ei_pending;
+
         let pc = self.registers.read16(Reg16::PC);
-        let opcode = bus.read(pc).unwrap();
+        let opcode = self.read_bus(bus, pc)?;
 
-        self.registers.write16(Reg16::PC, pc.wrapping_add(1));
-        let instr = self.decode(opcode, bus);
+        // the HALT bug makes the CPU fail to increment PC past the next opcode
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.write16(Reg16::PC, pc.wrapping_add(1));
+        }
+
+        if log_enabled!(Level::Debug) {
+            debug!("${:04X} {}", pc, disassembler::render(bus, pc).0);
+        }
+
+        let instr = self.decode(opcode, bus)?;
 
-        instr.execute((self, bus))
+        let cycles = instr.execute((&mut *self, &mut *bus))?;
+
+        if self.state == State::Locked {
+            // the opcode was still fetched, in either timing mode
+            self.charge(bus, cycles);
+            return Err(EmuError::IllegalOpcode { opcode, addr: pc });
+        }
+
+        if enable_ime && self.ei_pending {
+            self.ime = true;
+            self.ei_pending = false;
+        }
+
+        Ok(cycles)
+    }
+
+    fn pending_interrupt<B: MemoryBus>(&self, bus: &B) -> Result<Option<Interrupt>> {
+        let enable = bus.read(IE_ADDR)?;
+        let flag = bus.read(IF_ADDR)?;
+
+        Ok(Interrupt::from_bits_truncate(enable & flag).highest_priority())
     }
 
-    fn next_u8<B: MemoryBus>(&mut self, bus: &mut B) -> u8 {
+    fn joypad_pressed<B: MemoryBus>(&self, bus: &B) -> Result<bool> {
+        let flag = bus.read(IF_ADDR)?;
+
+        Ok(Interrupt::from_bits_truncate(flag).contains(Interrupt::JOYPAD))
+    }
+
+    /// Spends one M-cycle without touching memory
+    fn idle<B: MemoryBus>(&mut self, bus: &mut B) {
+        if self.timing == Timing::MCycle {
+            bus.tick(4);
+            self.ticked += 4;
+        }
+    }
+
+    fn read_bus<B: MemoryBus>(&mut self, bus: &mut B, addr: u16) -> Result<u8> {
+        self.idle(bus);
+        bus.read(addr)
+    }
+
+    fn write_bus<B: MemoryBus>(&mut self, bus: &mut B, addr: u16, val: u8) -> Result<()> {
+        self.idle(bus);
+        bus.write(addr, val)
+    }
+
+    fn next_u8<B: MemoryBus>(&mut self, bus: &mut B) -> Result<u8> {
         let pc = self.registers.read16(Reg16::PC);
         self.registers.write16(Reg16::PC, pc.wrapping_add(1));
-        bus.read(pc).unwrap()
+        self.read_bus(bus, pc)
     }
 
-    fn next_u16<B: MemoryBus>(&mut self, bus: &mut B) -> u16 {
-        let l = self.next_u8(bus);
-        let h = self.next_u8(bus);
-        ((h as u16) << 8) | (l as u16)
+    fn next_u16<B: MemoryBus>(&mut self, bus: &mut B) -> Result<u16> {
+        let l = self.next_u8(bus)?;
+        let h = self.next_u8(bus)?;
+        Ok(((h as u16) << 8) | (l as u16))
     }
 
-    fn push_u8<B: MemoryBus>(&mut self, bus: &mut B, val: u8) {
+    fn push_u8<B: MemoryBus>(&mut self, bus: &mut B, val: u8) -> Result<()> {
         let sp = self.registers.read16(Reg16::SP);
         self.registers.write16(Reg16::SP, sp.wrapping_sub(1));
-        bus.write(self.registers.read16(Reg16::SP), val).unwrap()
+        self.write_bus(bus, sp.wrapping_sub(1), val)
     }
 
-    fn push_u16<B: MemoryBus>(&mut self, bus: &mut B, val: u16) {
-        self.push_u8(bus, (val >> 8) as u8);
-        self.push_u8(bus, val as u8);
+    fn push_u16<B: MemoryBus>(&mut self, bus: &mut B, val: u16) -> Result<()> {
+        self.push_u8(bus, (val >> 8) as u8)?;
+        self.push_u8(bus, val as u8)
     }
 
-    fn pop_u8<B: MemoryBus>(&mut self, bus: &mut B) -> u8 {
+    fn pop_u8<B: MemoryBus>(&mut self, bus: &mut B) -> Result<u8> {
         let sp = self.registers.read16(Reg16::SP);
-        let val = bus.read(sp).unwrap();
+        let val = self.read_bus(bus, sp)?;
         self.registers.write16(Reg16::SP, sp.wrapping_add(1));
-        val
+        Ok(val)
+    }
+
+    fn pop_u16<B: MemoryBus>(&mut self, bus: &mut B) -> Result<u16> {
+        let l = self.pop_u8(bus)?;
+        let h = self.pop_u8(bus)?;
+        Ok((h as u16) << 8 | (l as u16))
+    }
+
+    fn read_loc<B: MemoryBus>(&mut self, bus: &mut B, loc: Loc8) -> Result<u8> {
+        match loc {
+            Loc8::Reg8(reg) => Ok(self.registers.read8(reg)),
+            Loc8::IndHL => {
+                let addr = self.registers.read16(Reg16::HL);
+                self.read_bus(bus, addr)
+            }
+        }
+    }
+
+    fn write_loc<B: MemoryBus>(&mut self, bus: &mut B, loc: Loc8, val: u8) -> Result<()> {
+        match loc {
+            Loc8::Reg8(reg) => {
+                self.registers.write8(reg, val);
+                Ok(())
+            }
+            Loc8::IndHL => {
+                let addr = self.registers.read16(Reg16::HL);
+                self.write_bus(bus, addr, val)
+            }
+        }
     }
 
-    fn pop_u16<B: MemoryBus>(&mut self, bus: &mut B) -> u16 {
-        let l = self.pop_u8(bus);
-        let h = self.pop_u8(bus);
-        ((h as u16) << 8 | (l as u16))
+    fn read_src<B: MemoryBus>(&mut self, bus: &mut B, src: Src) -> Result<u16> {
+        let addr = match src {
+            Src::D8(val) => return Ok(val as u16),
+            Src::D16(val) => return Ok(val),
+            Src::Reg8(reg) => return Ok(self.registers.read8(reg) as u16),
+            Src::Reg16(reg) => return Ok(self.registers.read16(reg)),
+            Src::PagedA8(val) => 0xFF00u16 | val as u16,
+            Src::PagedReg8(reg) => 0xFF00u16 | self.registers.read8(reg) as u16,
+            Src::Ind(reg) => self.registers.read16(reg),
+            Src::Reg16Inc(reg) => {
+                let addr = self.registers.read16(reg);
+                self.registers.write16(reg, addr.wrapping_add(1));
+                addr
+            }
+            Src::Reg16Dec(reg) => {
+                let addr = self.registers.read16(reg);
+                self.registers.write16(reg, addr.wrapping_sub(1));
+                addr
+            }
+            Src::Addr(addr) => addr,
+        };
+
+        Ok(self.read_bus(bus, addr)? as u16)
+    }
+
+    /// Shared body of the rotate, shift and swap instructions: `op` maps the
+    /// operand to its new value and the bit that was shifted out into carry.
+    fn shift<B, F>(&mut self, bus: &mut B, loc: Loc8, set_zero: bool, op: F) -> Result<()>
+        where
+            B: MemoryBus,
+            F: FnOnce(u8) -> (u8, bool),
+    {
+        let (val, carry) = op(self.read_loc(bus, loc)?);
+
+        self.registers.f = Flags::ZERO.self_or_empty(set_zero && val == 0)
+            | Flags::CARRY.self_or_empty(carry);
+
+        self.write_loc(bus, loc, val)
+    }
+}
+
+impl Default for LR35902 {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl InstructionDecoding for LR35902 {
-    fn decode<B: MemoryBus>(&mut self, opcode: u8, bus: &mut B) -> Instruction {
-        use self::Instruction::*;
-
-        match opcode {
-            0x0E => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg8(Reg8::C),
-                Src::D8(self.next_u8(bus)),
-            ),
-            0x05 => Dec(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::B,
-            ),
-            0x11 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 3,
-                    cycle_duration: 12,
-                },
-                Dst::Reg16(Reg16::DE),
-                Src::D16(self.next_u16(bus)),
-            ),
-            0x13 => Inc16(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Reg16::DE,
-            ),
-            0x18 => Jump(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                self.next_u8(bus) as i8,
-            ),
-            0x1A => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg8(Reg8::A),
-                Src::Reg16(Reg16::DE),
-            ),
-            0x57 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Dst::Reg8(Reg8::D),
-                Src::Reg8(Reg8::A),
-            ),
-            0x67 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Dst::Reg8(Reg8::H),
-                Src::Reg8(Reg8::A),
-            ),
-            0x20 => JumpOn(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                JumpCondition::NZ,
-                self.next_u8(bus) as i8,
-            ),
-            0x21 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 3,
-                    cycle_duration: 12,
-                },
-                Dst::Reg16(Reg16::HL),
-                Src::D16(self.next_u16(bus)),
-            ),
-            0x23 => Inc16(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Reg16::HL,
-            ),
-            0x31 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 3,
-                    cycle_duration: 12,
-                },
-                Dst::Reg16(Reg16::SP),
-                Src::D16(self.next_u16(bus)),
-            ),
-            0x22 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg16Inc(Reg16::HL),
-                Src::Reg8(Reg8::A),
-            ),
-            0x32 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg16Dec(Reg16::HL),
-                Src::Reg8(Reg8::A),
-            ),
-            0x3D => Dec(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::A,
-            ),
-            0x3E => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg8(Reg8::A),
-                Src::D8(self.next_u8(bus)),
-            ),
-            0x4F => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Dst::Reg8(Reg8::C),
-                Src::Reg8(Reg8::A),
-            ),
-            0x06 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 8,
-                },
-                Dst::Reg8(Reg8::B),
-                Src::D8(self.next_u8(bus)),
-            ),
-            0xAF => Xor(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::A,
-            ),
-            0x1E => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 8,
-                },
-                Dst::Reg8(Reg8::E),
-                Src::D8(self.next_u8(bus)),
-            ),
-            0xE2 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::PagedReg8(Reg8::C),
-                Src::Reg8(Reg8::A),
-            ),
-            0x04 => Inc(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::B,
-            ),
-            0x0C => Inc(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::C,
-            ),
-            0x0D => Dec(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::C,
-            ),
-            0x28 => JumpOn(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                JumpCondition::Z,
-                self.next_u8(bus) as i8,
-            ),
-            0x3C => Inc(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Reg8::A,
-            ),
-            0x7B => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                Dst::Reg8(Reg8::A),
-                Src::Reg8(Reg8::E),
-            ),
-            0x77 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 8,
-                },
-                Dst::Reg16(Reg16::HL),
-                Src::Reg8(Reg8::A),
-            ),
-            0xC1 => Pop16(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 12,
-                },
-                Reg16::BC,
-            ),
-            0xCD => Call(
-                Info {
-                    opcode: opcode,
-                    byte_length: 3,
-                    cycle_duration: 24,
-                },
-                self.next_u16(bus),
-            ),
-            0xC5 => Push16(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 16,
-                },
-                Reg16::BC,
-            ),
-            0xC9 => Ret(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 16,
-                },
-                self.pop_u16(bus),
-            ),
-            0xEA => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 3,
-                    cycle_duration: 16,
-                },
-                Dst::Addr(self.next_u16(bus)),
-                Src::Reg8(Reg8::A),
-            ),
-            0x2E => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                Dst::Reg8(Reg8::L),
-                Src::D8(self.next_u8(bus)),
-            ),
-            0xE0 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                Dst::A8(self.next_u8(bus)),
-                Src::Reg8(Reg8::A),
-            ),
-            0xF0 => Load(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 12,
-                },
-                Dst::Reg8(Reg8::A),
-                Src::PagedA8(self.next_u8(bus)),
-            ),
-            0xFE => Compare(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 8,
-                },
-                self.next_u8(bus),
-            ),
-            0x17 => RotateLeftAkku(
-                Info {
-                    opcode: opcode,
-                    byte_length: 1,
-                    cycle_duration: 4,
-                },
-                false,
-            ),
-            0xCB => PrefixCB,
-            0x00 => Nop(Info {
-                opcode: 0x00,
-                byte_length: 1,
-                cycle_duration: 4,
-            }),
-            _ => panic!("unrecognized opcode: {:02X}", opcode),
-        }
-    }
-
-    fn decode_cb<B: MemoryBus>(&mut self, opcode: u8, _: &mut B) -> Instruction {
-        use self::Instruction::*;
-
-        match opcode {
-            0x11 => RotateLeft(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 8,
-                },
-                Reg8::C,
-                true,
-            ),
-            0x7C => Bit(
-                Info {
-                    opcode: opcode,
-                    byte_length: 2,
-                    cycle_duration: 8,
-                },
-                7,
-                Reg8::H,
-            ),
-            _ => panic!("Unrecognized cb opcode: {:02X}", opcode),
+    fn decode<B: MemoryBus>(&mut self, opcode: u8, bus: &mut B) -> Result<Instruction> {
+        let spec = &OPCODES[opcode as usize];
+
+        if spec.mnemonic == Mnemonic::Prefix {
+            let opcode = self.next_u8(bus)?;
+            return self.decode_cb(opcode, bus);
         }
+
+        let imm = self.immediate(spec, bus)?;
+
+        // STOP is followed by a padding byte that is skipped over without
+        // spending a bus cycle on it
+        let pc = self.registers.read16(Reg16::PC);
+        let padding = spec.byte_length - 1 - spec.immediate_len();
+        self.registers.write16(Reg16::PC, pc.wrapping_add(padding as u16));
+
+        Ok(instruction(opcode, spec, imm))
+    }
+
+    fn decode_cb<B: MemoryBus>(&mut self, opcode: u8, _: &mut B) -> Result<Instruction> {
+        Ok(instruction(opcode, &CB_OPCODES[opcode as usize], 0))
+    }
+}
+
+impl LR35902 {
+    /// Fetches the immediate operand of `spec`, if it has one
+    fn immediate<B: MemoryBus>(&mut self, spec: &Opcode, bus: &mut B) -> Result<u16> {
+        Ok(match spec.immediate_len() {
+            0 => 0,
+            1 => self.next_u8(bus)? as u16,
+            _ => self.next_u16(bus)?,
+        })
+    }
+}
+
+/// Builds the instruction described by a table entry and its immediate
+fn instruction(opcode: u8, spec: &Opcode, imm: u16) -> Instruction {
+    use self::Instruction::*;
+    use self::Mnemonic as M;
+
+    let info = Info {
+        opcode,
+        byte_length: spec.byte_length,
+        cycle_duration: spec.cycle_duration,
+        branch_penalty: spec.branch_penalty,
+    };
+    let offset = imm as u8 as i8;
+
+    match (spec.mnemonic, spec.operands) {
+        (M::Nop, _) => Nop(info),
+        (M::Stop, _) => Stop(info),
+        (M::Halt, _) => Halt(info),
+        (M::Illegal, _) => Lock(info),
+        (M::Di, _) => DisableInterrupts(info),
+        (M::Ei, _) => EnableInterrupts(info),
+
+        (M::Ld, &[Operand::Reg16(Reg16::HL), Operand::SPOffset]) => LoadHLSP(info, offset),
+        (M::Ld, &[Operand::IndA16, Operand::Reg16(Reg16::SP)]) => {
+            Load(info, Dst::Addr16(imm), Src::Reg16(Reg16::SP))
+        }
+        (M::Ld, &[dst, src]) | (M::Ldh, &[dst, src]) => Load(info, dst_of(dst, imm), src_of(src, imm)),
+
+        (M::Inc, &[Operand::Reg16(reg)]) => Inc16(info, reg),
+        (M::Inc, &[loc]) => Inc(info, loc_of(loc)),
+        (M::Dec, &[Operand::Reg16(reg)]) => Dec16(info, reg),
+        (M::Dec, &[loc]) => Dec(info, loc_of(loc)),
+
+        (M::Add, &[Operand::Reg16(Reg16::HL), Operand::Reg16(reg)]) => AddHL(info, reg),
+        (M::Add, &[Operand::Reg16(Reg16::SP), Operand::R8]) => AddSP(info, offset),
+        (M::Add, &[_, src]) => Add(info, src_of(src, imm)),
+        (M::Adc, &[_, src]) => AddCarry(info, src_of(src, imm)),
+        (M::Sub, &[src]) => Sub(info, src_of(src, imm)),
+        (M::Sbc, &[_, src]) => SubCarry(info, src_of(src, imm)),
+        (M::And, &[src]) => And(info, src_of(src, imm)),
+        (M::Xor, &[src]) => Xor(info, src_of(src, imm)),
+        (M::Or, &[src]) => Or(info, src_of(src, imm)),
+        (M::Cp, &[src]) => Compare(info, src_of(src, imm)),
+
+        (M::Daa, _) => DecimalAdjust(info),
+        (M::Cpl, _) => Complement(info),
+        (M::Scf, _) => SetCarry(info),
+        (M::Ccf, _) => ComplementCarry(info),
+        (M::Rlca, _) => RotateLeftCircularAkku(info),
+        (M::Rrca, _) => RotateRightCircularAkku(info),
+        (M::Rla, _) => RotateLeftAkku(info, false),
+        (M::Rra, _) => RotateRightAkku(info),
+
+        (M::Jr, &[Operand::Cond(cond), _]) => JumpOn(info, cond, offset),
+        (M::Jr, _) => Jump(info, offset),
+        (M::Jp, &[Operand::Reg16(Reg16::HL)]) => JumpHL(info),
+        (M::Jp, &[Operand::Cond(cond), _]) => JumpAbsOn(info, cond, imm),
+        (M::Jp, _) => JumpAbs(info, imm),
+        (M::Call, &[Operand::Cond(cond), _]) => CallOn(info, cond, imm),
+        (M::Call, _) => Call(info, imm),
+        (M::Ret, &[Operand::Cond(cond)]) => RetOn(info, cond),
+        (M::Ret, _) => Ret(info),
+        (M::Reti, _) => RetInterrupt(info),
+        (M::Rst, &[Operand::Vector(addr)]) => Restart(info, addr),
+        (M::Push, &[Operand::Reg16(reg)]) => Push16(info, reg),
+        (M::Pop, &[Operand::Reg16(reg)]) => Pop16(info, reg),
+
+        (M::Rlc, &[loc]) => RotateLeftCircular(info, loc_of(loc), true),
+        (M::Rrc, &[loc]) => RotateRightCircular(info, loc_of(loc), true),
+        (M::Rl, &[loc]) => RotateLeft(info, loc_of(loc), true),
+        (M::Rr, &[loc]) => RotateRight(info, loc_of(loc), true),
+        (M::Sla, &[loc]) => ShiftLeftArithmetic(info, loc_of(loc)),
+        (M::Sra, &[loc]) => ShiftRightArithmetic(info, loc_of(loc)),
+        (M::Swap, &[loc]) => Swap(info, loc_of(loc)),
+        (M::Srl, &[loc]) => ShiftRightLogical(info, loc_of(loc)),
+        (M::Bit, &[Operand::BitIndex(bit), loc]) => Bit(info, bit as usize, loc_of(loc)),
+        (M::Res, &[Operand::BitIndex(bit), loc]) => Reset(info, bit as usize, loc_of(loc)),
+        (M::Set, &[Operand::BitIndex(bit), loc]) => Set(info, bit as usize, loc_of(loc)),
+
+        (mnemonic, operands) => unreachable!("malformed opcode table entry {:?} {:?}", mnemonic, operands),
+    }
+}
+
+fn loc_of(operand: Operand) -> Loc8 {
+    match operand {
+        Operand::Reg8(reg) => Loc8::Reg8(reg),
+        Operand::Ind(Reg16::HL) => Loc8::IndHL,
+        _ => unreachable!("{:?} is not an 8-bit location", operand),
+    }
+}
+
+fn src_of(operand: Operand, imm: u16) -> Src {
+    match operand {
+        Operand::Reg8(reg) => Src::Reg8(reg),
+        Operand::Reg16(reg) => Src::Reg16(reg),
+        Operand::Ind(reg) => Src::Ind(reg),
+        Operand::IndInc => Src::Reg16Inc(Reg16::HL),
+        Operand::IndDec => Src::Reg16Dec(Reg16::HL),
+        Operand::PagedC => Src::PagedReg8(Reg8::C),
+        Operand::PagedA8 => Src::PagedA8(imm as u8),
+        Operand::IndA16 => Src::Addr(imm),
+        Operand::D8 => Src::D8(imm as u8),
+        Operand::D16 => Src::D16(imm),
+        _ => unreachable!("{:?} is not a source operand", operand),
+    }
+}
+
+fn dst_of(operand: Operand, imm: u16) -> Dst {
+    match operand {
+        Operand::Reg8(reg) => Dst::Reg8(reg),
+        Operand::Reg16(reg) => Dst::Reg16(reg),
+        Operand::Ind(reg) => Dst::Ind(reg),
+        Operand::IndInc => Dst::Reg16Inc(Reg16::HL),
+        Operand::IndDec => Dst::Reg16Dec(Reg16::HL),
+        Operand::PagedC => Dst::PagedReg8(Reg8::C),
+        Operand::PagedA8 => Dst::A8(imm as u8),
+        Operand::IndA16 => Dst::Addr(imm),
+        _ => unreachable!("{:?} is not a destination operand", operand),
     }
 }
 
@@ -438,20 +453,97 @@ impl<'a, B> Ops for (&'a mut LR35902, &'a mut B)
     where
         B: MemoryBus,
 {
-    fn nop(self) {}
+    fn nop(self) -> Result<()> {
+        Ok(())
+    }
 
-    fn bit(self, bit: usize, reg: Reg8) {
+    fn stop(self) -> Result<()> {
+        let (cpu, bus) = self;
+
+        // on CGB, STOP with a speed switch armed in KEY1 only switches speed
+        if !bus.speed_switch() {
+            cpu.state = State::Stopped;
+        }
+
+        Ok(())
+    }
+
+    fn halt(self) -> Result<()> {
+        let (cpu, bus) = self;
+
+        if !cpu.ime && cpu.pending_interrupt(bus)?.is_some() {
+            cpu.halt_bug = true;
+        } else {
+            cpu.state = State::Halted;
+        }
+
+        Ok(())
+    }
+
+    fn lock(self) -> Result<()> {
         let (cpu, _) = self;
-        let val = cpu.registers.read8(reg) & (1 << bit);
+        cpu.state = State::Locked;
+
+        // unused opcodes hang the CPU for good: keep PC pointing at the
+        // offending opcode so every further step fetches it again
+        let pc = cpu.registers.read16(Reg16::PC);
+        cpu.registers.write16(Reg16::PC, pc.wrapping_sub(1));
+
+        Ok(())
+    }
+
+    fn di(self) -> Result<()> {
+        let (cpu, _) = self;
+        cpu.ime = false;
+        cpu.ei_pending = false;
+
+        Ok(())
+    }
+
+    fn ei(self) -> Result<()> {
+        let (cpu, _) = self;
+        cpu.ei_pending = true;
+
+        Ok(())
+    }
+
+    fn isr(self, irq: Interrupt) -> Result<()> {
+        let (cpu, bus) = self;
+        let flag = bus.read(IF_ADDR)?;
+        bus.write(IF_ADDR, flag & !irq.bits())?;
+
+        // dispatch waits two M-cycles before pushing PC, call covers one
+        cpu.ime = false;
+        cpu.idle(bus);
+        (cpu, bus).call(irq.vector())
+    }
+
+    fn bit(self, bit: usize, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_loc(bus, loc)? & (1 << bit);
 
         cpu.registers.f = Flags::ZERO.self_or_empty(val == 0)
             | Flags::HALF_CARRY
             | (Flags::CARRY & cpu.registers.f);
+
+        Ok(())
     }
 
-    fn dec(self, reg: Reg8) {
-        let (cpu, _) = self;
-        let val = cpu.registers.read8(reg);
+    fn res(self, bit: usize, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_loc(bus, loc)? & !(1 << bit);
+        cpu.write_loc(bus, loc, val)
+    }
+
+    fn set(self, bit: usize, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_loc(bus, loc)? | (1 << bit);
+        cpu.write_loc(bus, loc, val)
+    }
+
+    fn dec(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_loc(bus, loc)?;
         let new_val = val.wrapping_sub(1);
 
         cpu.registers.f = Flags::ZERO.self_or_empty(new_val == 0)
@@ -459,150 +551,989 @@ impl<'a, B> Ops for (&'a mut LR35902, &'a mut B)
             | Flags::HALF_CARRY.self_or_empty(val & 0xf == 0)
             | (Flags::CARRY & cpu.registers.f);
 
-        cpu.registers.write8(reg, new_val);
+        cpu.write_loc(bus, loc, new_val)
     }
 
-    fn inc(self, reg: Reg8) {
-        let (cpu, _) = self;
-        let val = cpu.registers.read8(reg);
+    fn inc(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_loc(bus, loc)?;
         let new_val = val.wrapping_add(1);
 
         cpu.registers.f = Flags::ZERO.self_or_empty(new_val == 0)
             | Flags::HALF_CARRY.self_or_empty(val & 0xf == 0xf)
             | (Flags::CARRY & cpu.registers.f);
 
-        cpu.registers.write8(reg, new_val);
+        cpu.write_loc(bus, loc, new_val)
     }
 
-    fn load(self, dst: Dst, src: Src) {
+    fn load(self, dst: Dst, src: Src) -> Result<()> {
         let (cpu, bus) = self;
 
-        let val: u16 = match src {
-            Src::PagedA8(val) => bus.read(0xFF00u16 | (val as u16)).unwrap() as u16,
-            Src::D8(val) => val as u16,
-            Src::D16(val) => val,
-            Src::Reg8(reg) => cpu.registers.read8(reg) as u16,
-            Src::Reg16(reg) => cpu.registers.read16(reg),
-        };
+        let val: u16 = cpu.read_src(bus, src)?;
 
-        match dst {
-            Dst::A8(val) => {
-                let addr = 0xFF00u16 | val as u16;
-                bus.write(addr, cpu.registers.read8(Reg8::A))
+        let addr = match dst {
+            Dst::Reg8(reg) => {
+                cpu.registers.write8(reg, val as u8);
+                return Ok(());
             }
-            Dst::Reg8(reg) => Ok(cpu.registers.write8(reg, val as u8)),
-            Dst::PagedReg8(reg) => {
-                let addr = 0xFF00u16 | cpu.registers.read8(reg) as u16;
-                bus.write(addr, cpu.registers.read8(Reg8::A))
+            Dst::Reg16(reg) => {
+                cpu.registers.write16(reg, val);
+                return Ok(());
             }
-            Dst::Reg16(reg) => Ok(cpu.registers.write16(reg, val)),
+            Dst::A8(addr) => 0xFF00u16 | addr as u16,
+            Dst::PagedReg8(reg) => 0xFF00u16 | cpu.registers.read8(reg) as u16,
+            Dst::Ind(reg) => cpu.registers.read16(reg),
             Dst::Reg16Inc(reg) => {
                 let addr = cpu.registers.read16(reg);
                 cpu.registers.write16(reg, addr.wrapping_add(1));
-                bus.write(addr, val as u8)
+                addr
             }
             Dst::Reg16Dec(reg) => {
                 let addr = cpu.registers.read16(reg);
                 cpu.registers.write16(reg, addr.wrapping_sub(1));
-                bus.write(addr, val as u8)
+                addr
+            }
+            Dst::Addr(addr) => addr,
+            Dst::Addr16(addr) => {
+                cpu.write_bus(bus, addr, val as u8)?;
+                return cpu.write_bus(bus, addr.wrapping_add(1), (val >> 8) as u8);
             }
-            Dst::Addr(addr) => bus.write(addr, val as u8),
         };
+
+        cpu.write_bus(bus, addr, val as u8)
     }
 
-    fn xor(self, reg: Reg8) {
+    fn load_hl_sp(self, offset: i8) -> Result<()> {
         let (cpu, _) = self;
-        let v = cpu.registers.read8(reg);
-        cpu.registers.write8(reg, v ^ v);
-        cpu.registers.f = Flags::ZERO.self_or_empty(cpu.registers.read8(Reg8::A) == 0)
+        let (val, flags) = alu::add_offset(cpu.registers.read16(Reg16::SP), offset);
+
+        cpu.registers.f = flags;
+        cpu.registers.write16(Reg16::HL, val);
+
+        Ok(())
+    }
+
+    fn add(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (res, flags) = alu::add(cpu.registers.read8(Reg8::A), val, false);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
     }
 
-    fn call(self, addr: u16) {
+    fn adc(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let carry = cpu.registers.f.contains(Flags::CARRY);
+        let (res, flags) = alu::add(cpu.registers.read8(Reg8::A), val, carry);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn sub(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (res, flags) = alu::sub(cpu.registers.read8(Reg8::A), val, false);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn sbc(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let carry = cpu.registers.f.contains(Flags::CARRY);
+        let (res, flags) = alu::sub(cpu.registers.read8(Reg8::A), val, carry);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn and(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (res, flags) = alu::and(cpu.registers.read8(Reg8::A), val);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn xor(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (res, flags) = alu::xor(cpu.registers.read8(Reg8::A), val);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn or(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (res, flags) = alu::or(cpu.registers.read8(Reg8::A), val);
+
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
+
+        Ok(())
+    }
+
+    fn cp(self, src: Src) -> Result<()> {
+        let (cpu, bus) = self;
+        let val = cpu.read_src(bus, src)? as u8;
+        let (_, flags) = alu::sub(cpu.registers.read8(Reg8::A), val, false);
+
+        cpu.registers.f = flags;
+
+        Ok(())
+    }
+
+    fn add_hl(self, reg: Reg16) -> Result<()> {
+        let (cpu, _) = self;
+        let hl = cpu.registers.read16(Reg16::HL);
+        let (val, flags) = alu::add16(hl, cpu.registers.read16(reg));
+
+        cpu.registers.f = (Flags::ZERO & cpu.registers.f) | flags;
+        cpu.registers.write16(Reg16::HL, val);
+
+        Ok(())
+    }
+
+    fn add_sp(self, offset: i8) -> Result<()> {
+        let (cpu, _) = self;
+        let (val, flags) = alu::add_offset(cpu.registers.read16(Reg16::SP), offset);
+
+        cpu.registers.f = flags;
+        cpu.registers.write16(Reg16::SP, val);
+
+        Ok(())
+    }
+
+    fn call(self, addr: u16) -> Result<()> {
         let (cpu, bus) = self;
         let pc = cpu.registers.read16(Reg16::PC);
-        cpu.push_u16(bus, pc);
+        cpu.idle(bus);
+        cpu.push_u16(bus, pc)?;
         cpu.registers.write16(Reg16::PC, addr);
+
+        Ok(())
     }
 
-    fn jr_c(self, cond: JumpCondition, offset: i8) {
-        let (cpu, _) = self;
+    fn call_c(self, cond: JumpCondition, addr: u16) -> Result<bool> {
+        let taken = cond.check(self.0.registers.f);
+        if taken {
+            self.call(addr)?;
+        }
+        Ok(taken)
+    }
 
-        if cond.check(cpu.registers.f) {
-            let addr = cpu.registers.read16(Reg16::PC).wrapping_add(offset as u16);
-            cpu.registers.write16(Reg16::PC, addr);
+    fn jr_c(self, cond: JumpCondition, offset: i8) -> Result<bool> {
+        let taken = cond.check(self.0.registers.f);
+        if taken {
+            self.jr(offset)?;
         }
+        Ok(taken)
     }
 
-    fn jr(self, offset: i8) {
-        let (cpu, _) = self;
+    fn jr(self, offset: i8) -> Result<()> {
+        let (cpu, bus) = self;
         let addr = cpu.registers.read16(Reg16::PC).wrapping_add(offset as u16);
         cpu.registers.write16(Reg16::PC, addr);
+        cpu.idle(bus);
+
+        Ok(())
+    }
+
+    fn jp(self, addr: u16) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.registers.write16(Reg16::PC, addr);
+        cpu.idle(bus);
+
+        Ok(())
     }
 
-    fn ret(self, addr: u16) {
+    fn jp_c(self, cond: JumpCondition, addr: u16) -> Result<bool> {
+        let taken = cond.check(self.0.registers.f);
+        if taken {
+            self.jp(addr)?;
+        }
+        Ok(taken)
+    }
+
+    fn jp_hl(self) -> Result<()> {
         let (cpu, _) = self;
+        let addr = cpu.registers.read16(Reg16::HL);
         cpu.registers.write16(Reg16::PC, addr);
+
+        Ok(())
     }
 
-    fn push16(self, reg: Reg16) {
+    fn ret(self) -> Result<()> {
+        let (cpu, bus) = self;
+        let addr = cpu.pop_u16(bus)?;
+        cpu.registers.write16(Reg16::PC, addr);
+        cpu.idle(bus);
+
+        Ok(())
+    }
+
+    fn ret_c(self, cond: JumpCondition) -> Result<bool> {
+        let (cpu, bus) = self;
+
+        // evaluating the condition costs an M-cycle of its own
+        cpu.idle(bus);
+
+        let taken = cond.check(cpu.registers.f);
+        if taken {
+            (cpu, bus).ret()?;
+        }
+        Ok(taken)
+    }
+
+    fn reti(self) -> Result<()> {
+        // unlike EI, RETI enables interrupts right away
+        self.0.ime = true;
+        self.ret()
+    }
+
+    fn rst(self, addr: u8) -> Result<()> {
+        self.call(addr as u16)
+    }
+
+    fn push16(self, reg: Reg16) -> Result<()> {
         let (cpu, bus) = self;
         let val = cpu.registers.read16(reg);
-        cpu.push_u16(bus, val);
+        cpu.idle(bus);
+        cpu.push_u16(bus, val)
     }
 
-    fn pop16(self, reg: Reg16) {
+    fn pop16(self, reg: Reg16) -> Result<()> {
         let (cpu, bus) = self;
-        let val = cpu.pop_u16(bus);
+        let val = cpu.pop_u16(bus)?;
         cpu.registers.write16(reg, val);
-    }
 
-    fn rl(self, reg: Reg8, set_zero: bool) {
-        let (cpu, _) = self;
+        Ok(())
+    }
 
-        let reg_val = cpu.registers.read8(reg);
+    fn rl(self, loc: Loc8, set_zero: bool) -> Result<()> {
+        let (cpu, bus) = self;
         let carry_val = if cpu.registers.f.contains(Flags::CARRY) {
             1
         } else {
             0
         };
 
-        let new_carry_val = reg_val & 0x80;
-        let new_reg_val = (reg_val << 1) | carry_val;
+        cpu.shift(bus, loc, set_zero, |val| ((val << 1) | carry_val, val & 0x80 != 0))
+    }
+
+    fn rlc(self, loc: Loc8, set_zero: bool) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, set_zero, |val| (val.rotate_left(1), val & 0x80 != 0))
+    }
+
+    fn rr(self, loc: Loc8, set_zero: bool) -> Result<()> {
+        let (cpu, bus) = self;
+        let carry_val = if cpu.registers.f.contains(Flags::CARRY) {
+            0x80
+        } else {
+            0
+        };
+
+        cpu.shift(bus, loc, set_zero, |val| ((val >> 1) | carry_val, val & 0x01 != 0))
+    }
+
+    fn rrc(self, loc: Loc8, set_zero: bool) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, set_zero, |val| (val.rotate_right(1), val & 0x01 != 0))
+    }
+
+    fn sla(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, true, |val| (val << 1, val & 0x80 != 0))
+    }
+
+    fn sra(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, true, |val| ((val >> 1) | (val & 0x80), val & 0x01 != 0))
+    }
+
+    fn srl(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, true, |val| (val >> 1, val & 0x01 != 0))
+    }
+
+    fn swap(self, loc: Loc8) -> Result<()> {
+        let (cpu, bus) = self;
+        cpu.shift(bus, loc, true, |val| (val.rotate_left(4), false))
+    }
+
+    fn daa(self) -> Result<()> {
+        let (cpu, _) = self;
+        let (res, flags) = alu::daa(cpu.registers.read8(Reg8::A), cpu.registers.f);
 
-        cpu.registers.f = Flags::ZERO.self_or_empty(set_zero && new_reg_val == 0)
-            | Flags::CARRY.self_or_empty(new_carry_val != 0);
+        cpu.registers.f = flags;
+        cpu.registers.write8(Reg8::A, res);
 
-        cpu.registers.write8(reg, new_reg_val);
+        Ok(())
     }
 
-    fn inc16(self, reg: Reg16) {
+    fn cpl(self) -> Result<()> {
+        let (cpu, _) = self;
+        let val = cpu.registers.read8(Reg8::A);
+
+        cpu.registers.f = (Flags::ZERO & cpu.registers.f)
+            | Flags::ADD_SUB
+            | Flags::HALF_CARRY
+            | (Flags::CARRY & cpu.registers.f);
+
+        cpu.registers.write8(Reg8::A, !val);
+
+        Ok(())
+    }
+
+    fn scf(self) -> Result<()> {
+        let (cpu, _) = self;
+
+        cpu.registers.f = (Flags::ZERO & cpu.registers.f) | Flags::CARRY;
+
+        Ok(())
+    }
+
+    fn ccf(self) -> Result<()> {
+        let (cpu, _) = self;
+
+        cpu.registers.f = (Flags::ZERO & cpu.registers.f)
+            | Flags::CARRY.self_or_empty(!cpu.registers.f.contains(Flags::CARRY));
+
+        Ok(())
+    }
+
+    fn inc16(self, reg: Reg16) -> Result<()> {
         let (cpu, _) = self;
         let val = cpu.registers.read16(reg);
 
         cpu.registers.write16(reg, val.wrapping_add(1));
+
+        Ok(())
     }
 
-    fn sub(self, val: u8) {
+    fn dec16(self, reg: Reg16) -> Result<()> {
         let (cpu, _) = self;
-        let carry_val = if false && cpu.registers.f.contains(Flags::CARRY) {
-            1
-        } else {
-            0
-        };
+        let val = cpu.registers.read16(reg);
 
-        let reg_val = cpu.registers.read8(Reg8::A);
-        let sub_res = reg_val.wrapping_sub(val).wrapping_sub(carry_val);
+        cpu.registers.write16(reg, val.wrapping_sub(1));
 
-        cpu.registers.f = Flags::ZERO.self_or_empty(sub_res == 0)
-            | Flags::ADD_SUB
-            | Flags::CARRY.self_or_empty((reg_val as u16) < (val as u16) + (carry_val as u16))
-            | Flags::HALF_CARRY.self_or_empty((reg_val & 0xf) < (val & 0xf) + carry_val);
+        Ok(())
     }
+}
 
-    fn prefix_cb(self) -> Instruction {
-        let (cpu, bus) = self;
-        let opcode = cpu.next_u8(bus);
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// A flat 64 KiB address space, enough to run small programs through the CPU
+    struct Ram(Vec<u8>);
+
+    impl MemoryBus for Ram {
+        fn read(&self, addr: u16) -> Result<u8> {
+            Ok(self.0[addr as usize])
+        }
+
+        fn write(&mut self, addr: u16, val: u8) -> Result<()> {
+            self.0[addr as usize] = val;
+            Ok(())
+        }
+    }
+
+    /// Loads `program` at $0000 and executes `steps` instructions from there
+    fn run(cpu: &mut LR35902, program: &[u8], steps: usize) -> Ram {
+        let mut ram = Ram(vec![0; 0x10000]);
+        ram.0[..program.len()].copy_from_slice(program);
+
+        for _ in 0..steps {
+            cpu.step(&mut ram).unwrap();
+        }
+
+        ram
+    }
+
+    #[test]
+    fn ld_a16_sp_stores_little_endian() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0xBEEF);
+
+        let ram = run(&mut cpu, &[0x08, 0x00, 0xC0], 1);
+
+        assert_eq!(ram.0[0xC000], 0xEF);
+        assert_eq!(ram.0[0xC001], 0xBE);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0003);
+    }
+
+    #[test]
+    fn ld_sp_hl() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::HL, 0xD00D);
+
+        run(&mut cpu, &[0xF9], 1);
+
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0xD00D);
+    }
+
+    #[test]
+    fn dec16_wraps_and_keeps_flags() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO | Flags::CARRY;
+
+        run(&mut cpu, &[0x0B, 0x1B], 2);
+
+        assert_eq!(cpu.registers.read16(Reg16::BC), 0xFFFF);
+        assert_eq!(cpu.registers.read16(Reg16::DE), 0xFFFF);
+        assert_eq!(cpu.registers.f, Flags::ZERO | Flags::CARRY);
+    }
+
+    #[test]
+    fn add_hl_keeps_zero_flag() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO | Flags::ADD_SUB;
+        cpu.registers.write16(Reg16::HL, 0x8A23);
+
+        run(&mut cpu, &[0x29], 1);
+
+        assert_eq!(cpu.registers.read16(Reg16::HL), 0x1446);
+        assert_eq!(cpu.registers.f, Flags::ZERO | Flags::HALF_CARRY | Flags::CARRY);
+    }
+
+    #[test]
+    fn ld_hl_sp_e8() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO | Flags::ADD_SUB;
+        cpu.registers.write16(Reg16::SP, 0xFFF8);
+
+        run(&mut cpu, &[0xF8, 0x08], 1);
+
+        assert_eq!(cpu.registers.read16(Reg16::HL), 0x0000);
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0xFFF8);
+        assert_eq!(cpu.registers.f, Flags::HALF_CARRY | Flags::CARRY);
+    }
+
+    #[test]
+    fn add_sp_e8() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO | Flags::ADD_SUB;
+        cpu.registers.write16(Reg16::SP, 0xFFF8);
+
+        run(&mut cpu, &[0xE8, 0xFF], 1);
+
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0xFFF7);
+        assert_eq!(cpu.registers.f, Flags::HALF_CARRY | Flags::CARRY);
+    }
+
+    #[test]
+    fn pop_af_drops_low_nibble() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0x0010);
+
+        let mut ram = Ram(vec![0; 0x10000]);
+        ram.0[0x0000] = 0xF1;
+        ram.0[0x0010] = 0xFF;
+        ram.0[0x0011] = 0x12;
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.registers.read16(Reg16::AF), 0x12F0);
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0x0012);
+    }
+
+    fn program_ram(enable: u8, flag: u8, program: &[u8]) -> Ram {
+        let mut ram = Ram(vec![0; 0x10000]);
+        ram.0[..program.len()].copy_from_slice(program);
+        ram.0[IE_ADDR as usize] = enable;
+        ram.0[IF_ADDR as usize] = flag;
+        ram
+    }
+
+    #[test]
+    fn ei_is_delayed_by_one_instruction() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0xD000);
+        let mut ram = program_ram(0x1F, 0x04, &[0xFB, 0x00, 0x00]);
+
+        assert_eq!(cpu.step(&mut ram), Ok(4));
+        assert_eq!(cpu.step(&mut ram), Ok(4));
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0002);
+
+        assert_eq!(cpu.step(&mut ram), Ok(20));
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0050);
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0xCFFE);
+        assert_eq!(ram.0[0xCFFE], 0x02);
+        assert_eq!(ram.0[IF_ADDR as usize], 0x00);
+        assert!(!cpu.ime);
+    }
+
+    #[test]
+    fn di_right_after_ei_cancels_it() {
+        let mut cpu = LR35902::new();
+        let mut ram = program_ram(0x01, 0x01, &[0xFB, 0xF3, 0x00]);
+
+        for _ in 0..3 {
+            cpu.step(&mut ram).unwrap();
+        }
+
+        assert!(!cpu.ime);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0003);
+    }
+
+    #[test]
+    fn dispatch_picks_highest_priority() {
+        let mut cpu = LR35902::new();
+        cpu.ime = true;
+        cpu.registers.write16(Reg16::SP, 0xD000);
+        let mut ram = program_ram(0x1A, 0x1F, &[]);
+
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0048);
+        assert_eq!(ram.0[IF_ADDR as usize], 0x1D);
+    }
+
+    #[test]
+    fn reti_enables_interrupts_immediately() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0xCFFE);
+        let mut ram = program_ram(0x01, 0x01, &[0xD9]);
+        ram.0[0xCFFE] = 0x00;
+        ram.0[0xCFFF] = 0x20;
+
+        cpu.step(&mut ram).unwrap();
+        assert!(cpu.ime);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x2000);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0040);
+    }
+
+    #[test]
+    fn halt_waits_for_interrupt_without_ime() {
+        let mut cpu = LR35902::new();
+        let mut ram = program_ram(0x04, 0x00, &[0x76, 0x3C]);
 
-        cpu.decode_cb(opcode, bus).execute((cpu, bus))
+        cpu.step(&mut ram).unwrap();
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.state, State::Halted);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0001);
+
+        ram.0[IF_ADDR as usize] = 0x04;
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.state, State::Running);
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x01);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0002);
+    }
+
+    #[test]
+    fn halt_with_ime_services_interrupt() {
+        let mut cpu = LR35902::new();
+        cpu.ime = true;
+        cpu.registers.write16(Reg16::SP, 0xD000);
+        let mut ram = program_ram(0x01, 0x00, &[0x76, 0x00]);
+
+        cpu.step(&mut ram).unwrap();
+        ram.0[IF_ADDR as usize] = 0x01;
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0040);
+        assert_eq!(ram.0[0xCFFE], 0x01);
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        let mut cpu = LR35902::new();
+        let mut ram = program_ram(0x01, 0x01, &[0x76, 0x3C, 0x00]);
+
+        for _ in 0..3 {
+            cpu.step(&mut ram).unwrap();
+        }
+
+        assert_eq!(cpu.state, State::Running);
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x02);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0002);
+    }
+
+    #[test]
+    fn halt_bug_after_ei_returns_to_halt() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0xD000);
+        let mut ram = program_ram(0x01, 0x01, &[0xFB, 0x76, 0x00]);
+
+        cpu.step(&mut ram).unwrap();
+        cpu.step(&mut ram).unwrap();
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0040);
+        assert_eq!(ram.0[0xCFFE], 0x01);
+        assert!(!cpu.halt_bug);
+    }
+
+    #[test]
+    fn stop_waits_for_joypad() {
+        let mut cpu = LR35902::new();
+        let mut ram = program_ram(0x00, 0x00, &[0x10, 0x00, 0x3C]);
+
+        cpu.step(&mut ram).unwrap();
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.state, State::Stopped);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0002);
+
+        ram.0[IF_ADDR as usize] = 0x10;
+        cpu.step(&mut ram).unwrap();
+
+        assert_eq!(cpu.state, State::Running);
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x01);
+    }
+
+    #[test]
+    fn conditional_branch_costs() {
+        let mut cpu = LR35902::new();
+        let mut ram = Ram(vec![0; 0x10000]);
+
+        let costs: Vec<(usize, usize)> = [0x20, 0xC2, 0xC4, 0xC0]
+            .iter()
+            .map(|&opcode| {
+                let info = cpu.decode(opcode, &mut ram).unwrap().info();
+                (info.cycle_duration, info.branch_penalty)
+            })
+            .collect();
+
+        assert_eq!(costs, vec![(8, 4), (12, 4), (12, 12), (8, 12)]);
+    }
+
+    #[test]
+    fn step_returns_elapsed_cycles() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO;
+        cpu.registers.write16(Reg16::SP, 0xD000);
+
+        // JR NZ,+0 ; JR Z,+0 ; SET 0,(HL) ; BIT 0,B ; HALT
+        let mut ram = program_ram(0x00, 0x00, &[0x20, 0x00, 0x28, 0x00, 0xCB, 0xC6, 0xCB, 0x40, 0x76]);
+        cpu.registers.write16(Reg16::HL, 0xC000);
+
+        let cycles: Vec<usize> = (0..6).map(|_| cpu.step(&mut ram).unwrap()).collect();
+
+        assert_eq!(cycles, vec![8, 12, 16, 8, 4, 4]);
+    }
+
+    #[test]
+    fn illegal_opcode_is_reported_and_keeps_cpu_locked() {
+        for &timing in &[Timing::Batch, Timing::MCycle] {
+            let mut cpu = LR35902::new();
+            cpu.set_timing(timing);
+            let mut ram = clocked_ram(&[0x00, 0xD3]);
+            ram.ram[IE_ADDR as usize] = 0x04;
+            let fault = Err(EmuError::IllegalOpcode { opcode: 0xD3, addr: 0x0001 });
+
+            assert_eq!(cpu.step(&mut ram), Ok(4));
+            assert_eq!(cpu.step(&mut ram), fault);
+            // the fetch is charged whichever way the bus is ticked
+            assert_eq!(ram.clock, 8, "{:?}", timing);
+            assert_eq!(cpu.ticked(), 4);
+
+            // not even an interrupt gets it going again
+            cpu.ime = true;
+            ram.ram[IF_ADDR as usize] = 0x04;
+            assert_eq!(cpu.step(&mut ram), fault);
+            assert_eq!(ram.clock, 12, "{:?}", timing);
+            assert_eq!(cpu.registers.read16(Reg16::PC), 0x0001);
+        }
+    }
+
+    #[test]
+    fn conditions_check_carry() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::CARRY;
+
+        // JP NC,$1234 ; JP C,$4321
+        run(&mut cpu, &[0xD2, 0x34, 0x12, 0xDA, 0x21, 0x43], 2);
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x4321);
+    }
+
+    #[test]
+    fn call_cc_and_ret_cc() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO;
+        cpu.registers.write16(Reg16::SP, 0xD000);
+
+        let mut program = vec![0; 0x20];
+        // CALL NZ,$0010 ; CALL Z,$0010 ; ... $0010: RET NZ ; RET Z
+        program[..6].copy_from_slice(&[0xC4, 0x10, 0x00, 0xCC, 0x10, 0x00]);
+        program[0x10..0x12].copy_from_slice(&[0xC0, 0xC8]);
+
+        run(&mut cpu, &program, 4);
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0006);
+        assert_eq!(cpu.registers.read16(Reg16::SP), 0xD000);
+    }
+
+    #[test]
+    fn rst_and_jp_hl() {
+        let mut cpu = LR35902::new();
+        cpu.registers.write16(Reg16::SP, 0xD000);
+        cpu.registers.write16(Reg16::HL, 0x0003);
+
+        let mut program = vec![0; 0x40];
+        program[..4].copy_from_slice(&[0xE9, 0x00, 0x00, 0xFF]);
+
+        let ram = run(&mut cpu, &program, 2);
+
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0038);
+        assert_eq!(ram.0[0xCFFE], 0x04);
+    }
+
+    #[test]
+    fn accumulator_rotates_clear_zero() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO | Flags::ADD_SUB | Flags::HALF_CARRY;
+        cpu.registers.write8(Reg8::A, 0x80);
+
+        // RLCA ; RRCA ; RRA ; RRA
+        let mut ram = program_ram(0x00, 0x00, &[0x07, 0x0F, 0x1F, 0x1F]);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x01);
+        assert_eq!(cpu.registers.f, Flags::CARRY);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x80);
+        assert_eq!(cpu.registers.f, Flags::CARRY);
+
+        // with C clear, shifting out the last bit leaves Z clear
+        cpu.registers.f = Flags::empty();
+        cpu.registers.write8(Reg8::A, 0x01);
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x00);
+        assert_eq!(cpu.registers.f, Flags::CARRY);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read8(Reg8::A), 0x80);
+        assert_eq!(cpu.registers.f, Flags::empty());
+    }
+
+    #[test]
+    fn cpl_scf_ccf() {
+        let mut cpu = LR35902::new();
+        cpu.registers.f = Flags::ZERO;
+        cpu.registers.write8(Reg8::A, 0x35);
+
+        // CPL ; SCF ; CCF ; CCF
+        let mut ram = program_ram(0x00, 0x00, &[0x2F, 0x37, 0x3F, 0x3F]);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.read8(Reg8::A), 0xCA);
+        assert_eq!(cpu.registers.f, Flags::ZERO | Flags::ADD_SUB | Flags::HALF_CARRY);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.f, Flags::ZERO | Flags::CARRY);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.f, Flags::ZERO);
+
+        cpu.step(&mut ram).unwrap();
+        assert_eq!(cpu.registers.f, Flags::ZERO | Flags::CARRY);
+    }
+
+    /// Flat memory that keeps a T-cycle clock and records when each write
+    /// landed on it
+    struct ClockedRam {
+        ram: Vec<u8>,
+        clock: usize,
+        writes: Vec<(usize, u16)>,
+    }
+
+    impl MemoryBus for ClockedRam {
+        fn read(&self, addr: u16) -> Result<u8> {
+            Ok(self.ram[addr as usize])
+        }
+
+        fn write(&mut self, addr: u16, val: u8) -> Result<()> {
+            self.writes.push((self.clock, addr));
+            self.ram[addr as usize] = val;
+            Ok(())
+        }
+
+        fn tick(&mut self, cycles: usize) {
+            self.clock += cycles;
+        }
+    }
+
+    fn clocked_ram(program: &[u8]) -> ClockedRam {
+        let mut ram = vec![0; 0x10000];
+        ram[..program.len()].copy_from_slice(program);
+
+        ClockedRam {
+            ram,
+            clock: 0,
+            writes: Vec::new(),
+        }
+    }
+
+    fn write_times(timing: Timing) -> (Vec<(usize, u16)>, usize) {
+        let mut cpu = LR35902::new();
+        cpu.set_timing(timing);
+        cpu.registers.write16(Reg16::HL, 0xC000);
+        cpu.registers.write16(Reg16::SP, 0xD000);
+
+        // LD (HL),A ; PUSH BC ; CALL $0010
+        let mut ram = clocked_ram(&[0x77, 0xC5, 0xCD, 0x10, 0x00]);
+        let cycles: usize = (0..3).map(|_| cpu.step(&mut ram).unwrap()).sum();
+
+        assert_eq!(ram.clock, cycles);
+        (ram.writes, cycles)
+    }
+
+    #[test]
+    fn m_cycle_timing_places_writes_on_their_cycle() {
+        let (writes, cycles) = write_times(Timing::MCycle);
+
+        assert_eq!(cycles, 48);
+        assert_eq!(
+            writes,
+            vec![(8, 0xC000), (20, 0xCFFF), (24, 0xCFFE), (44, 0xCFFD), (48, 0xCFFC)]
+        );
+    }
+
+    #[test]
+    fn batch_timing_charges_whole_instructions() {
+        let (writes, cycles) = write_times(Timing::Batch);
+
+        assert_eq!(cycles, 48);
+        assert_eq!(
+            writes,
+            vec![(0, 0xC000), (8, 0xCFFF), (8, 0xCFFE), (24, 0xCFFD), (24, 0xCFFC)]
+        );
+    }
+
+    #[test]
+    fn m_cycle_timing_pushes_pc_after_two_wait_cycles_on_dispatch() {
+        let mut cpu = LR35902::new();
+        cpu.set_timing(Timing::MCycle);
+        cpu.ime = true;
+        cpu.registers.write16(Reg16::SP, 0xD000);
+
+        let mut ram = clocked_ram(&[]);
+        ram.ram[IE_ADDR as usize] = Interrupt::VBLANK.bits();
+        ram.ram[IF_ADDR as usize] = Interrupt::VBLANK.bits();
+
+        assert_eq!(cpu.step(&mut ram).unwrap(), 20);
+        assert_eq!(ram.clock, 20);
+        assert_eq!(ram.writes, vec![(0, IF_ADDR), (12, 0xCFFF), (16, 0xCFFE)]);
+        assert_eq!(cpu.registers.read16(Reg16::PC), 0x0040);
+    }
+
+    #[test]
+    fn m_cycle_timing_never_overruns_instruction_cost() {
+        for opcode in 0..=0xFFu8 {
+            for &flags in &[Flags::empty(), Flags::all()] {
+                let mut cpu = LR35902::new();
+                cpu.set_timing(Timing::MCycle);
+                cpu.registers.f = flags;
+                cpu.registers.write16(Reg16::SP, 0xD000);
+
+                let mut ram = clocked_ram(&[opcode, 0x46, 0x00]);
+                // an illegal opcode faults right after its fetch
+                let cycles = match cpu.step(&mut ram) {
+                    Ok(cycles) => cycles,
+                    Err(EmuError::IllegalOpcode { .. }) => 4,
+                    Err(err) => panic!("opcode {:02X}: {}", opcode, err),
+                };
+
+                assert_eq!(ram.clock, cycles, "opcode {:02X}", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn decoding_consumes_table_length() {
+        for opcode in 0..=0xFFu8 {
+            let mut cpu = LR35902::new();
+            let mut ram = program_ram(0x00, 0x00, &[opcode, 0xCB, 0x37, 0x12]);
+            cpu.registers.write16(Reg16::PC, 1);
+
+            let info = cpu.decode(opcode, &mut ram).unwrap().info();
+
+            assert_eq!(cpu.registers.read16(Reg16::PC) as usize, info.byte_length, "opcode {:02X}", opcode);
+        }
+
+        for opcode in 0..=0xFFu8 {
+            let mut cpu = LR35902::new();
+            let mut ram = program_ram(0x00, 0x00, &[0xCB, opcode]);
+            cpu.registers.write16(Reg16::PC, 1);
+
+            let info = cpu.decode(0xCB, &mut ram).unwrap().info();
+
+            assert_eq!(info.opcode, opcode);
+            assert_eq!(cpu.registers.read16(Reg16::PC) as usize, info.byte_length, "opcode CB {:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn flags_follow_table() {
+        let mut seed = 0x1234_5678u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        };
+
+        let entries = OPCODES.iter().enumerate().map(|(opcode, spec)| (vec![opcode as u8], spec));
+        let cb_entries = CB_OPCODES.iter().enumerate().map(|(opcode, spec)| (vec![0xCB, opcode as u8], spec));
+
+        for (program, spec) in entries.chain(cb_entries) {
+            if spec.mnemonic == Mnemonic::Prefix || spec.mnemonic == Mnemonic::Illegal {
+                continue;
+            }
+
+            for _ in 0..64 {
+                let mut cpu = LR35902::new();
+                for &reg in &[Reg16::AF, Reg16::BC, Reg16::DE, Reg16::HL] {
+                    cpu.registers.write16(reg, (next() as u16) << 8 | next() as u16);
+                }
+                cpu.registers.write16(Reg16::SP, 0xD000);
+
+                let mut program = program.clone();
+                program.extend_from_slice(&[next(), next()]);
+                let mut ram = program_ram(0x00, 0x00, &program);
+                ram.0[0xD000] = next();
+                ram.0[0xD001] = next();
+
+                let before = cpu.registers.f;
+                cpu.step(&mut ram).unwrap();
+                let after = cpu.registers.f;
+
+                let flags = [Flags::ZERO, Flags::ADD_SUB, Flags::HALF_CARRY, Flags::CARRY];
+                for (&flag, effect) in flags.iter().zip(spec.flags.chars()) {
+                    let expected = match effect {
+                        '0' => false,
+                        '1' => true,
+                        '-' => before.contains(flag),
+                        _ => continue,
+                    };
+
+                    assert_eq!(after.contains(flag), expected, "{:02X?} {:?} {:?}", program, flag, spec.mnemonic);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,755 @@
+//! Opcode metadata for the unprefixed and $CB-prefixed instruction sets.
+//! The decoder, the disassembler and the tests all read from these tables,
+//! so lengths and cycle counts only live in one place.
+
+use std::fmt;
+
+use super::instructions::JumpCondition;
+use super::registers::{Reg16, Reg8};
+
+use self::Mnemonic::*;
+use self::Operand::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mnemonic {
+    Nop,
+    Stop,
+    Halt,
+    Di,
+    Ei,
+    Ld,
+    Ldh,
+    Inc,
+    Dec,
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Jr,
+    Jp,
+    Call,
+    Ret,
+    Reti,
+    Rst,
+    Push,
+    Pop,
+    Prefix,
+    Illegal,
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+    Bit,
+    Res,
+    Set,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Operand {
+    Reg8(Reg8),
+    Reg16(Reg16),
+    /// Memory at the address held in a register pair
+    Ind(Reg16),
+    /// `(HL+)`
+    IndInc,
+    /// `(HL-)`
+    IndDec,
+    /// `($FF00+C)`
+    PagedC,
+    /// `($FF00+a8)`
+    PagedA8,
+    /// `(a16)`
+    IndA16,
+    D8,
+    D16,
+    /// An absolute jump or call target
+    A16,
+    /// A signed jump offset, relative to the next instruction
+    R8,
+    /// `SP+r8`
+    SPOffset,
+    Cond(JumpCondition),
+    BitIndex(u8),
+    Vector(u8),
+}
+
+impl Operand {
+    /// Number of immediate bytes following the opcode for this operand
+    pub fn immediate_len(&self) -> usize {
+        match *self {
+            PagedA8 | D8 | R8 | SPOffset => 1,
+            IndA16 | D16 | A16 => 2,
+            _ => 0,
+        }
+    }
+}
+
+pub struct Opcode {
+    pub mnemonic: Mnemonic,
+    pub operands: &'static [Operand],
+    /// Length in bytes, including the $CB prefix for the prefixed set
+    pub byte_length: usize,
+    /// Cycles taken, not counting a taken branch
+    pub cycle_duration: usize,
+    /// Extra cycles a conditional branch costs when it is taken
+    pub branch_penalty: usize,
+    /// Effect on Z, N, H and C in that order: `0` or `1` when forced, `-`
+    /// when left alone, the flag's letter when it depends on the result
+    pub flags: &'static str,
+}
+
+impl Opcode {
+    /// Number of immediate bytes following the opcode
+    pub fn immediate_len(&self) -> usize {
+        self.operands.iter().map(Operand::immediate_len).sum()
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+const fn op(
+    mnemonic: Mnemonic,
+    operands: &'static [Operand],
+    byte_length: usize,
+    cycle_duration: usize,
+    branch_penalty: usize,
+    flags: &'static str,
+) -> Opcode {
+    Opcode {
+        mnemonic,
+        operands,
+        byte_length,
+        cycle_duration,
+        branch_penalty,
+        flags,
+    }
+}
+
+const A: Operand = Reg8(Reg8::A);
+const B: Operand = Reg8(Reg8::B);
+const C: Operand = Reg8(Reg8::C);
+const D: Operand = Reg8(Reg8::D);
+const E: Operand = Reg8(Reg8::E);
+const H: Operand = Reg8(Reg8::H);
+const L: Operand = Reg8(Reg8::L);
+
+const AF: Operand = Reg16(Reg16::AF);
+const BC: Operand = Reg16(Reg16::BC);
+const DE: Operand = Reg16(Reg16::DE);
+const HL: Operand = Reg16(Reg16::HL);
+const SP: Operand = Reg16(Reg16::SP);
+
+const IND_BC: Operand = Ind(Reg16::BC);
+const IND_DE: Operand = Ind(Reg16::DE);
+const IND_HL: Operand = Ind(Reg16::HL);
+const HL_INC: Operand = IndInc;
+const HL_DEC: Operand = IndDec;
+
+const IF_NZ: Operand = Cond(JumpCondition::NZ);
+const IF_Z: Operand = Cond(JumpCondition::Z);
+const IF_NC: Operand = Cond(JumpCondition::NC);
+const IF_C: Operand = Cond(JumpCondition::C);
+
+pub static OPCODES: [Opcode; 256] = [
+    /* 00 */ op(Nop, &[], 1, 4, 0, "----"),
+    /* 01 */ op(Ld, &[BC, D16], 3, 12, 0, "----"),
+    /* 02 */ op(Ld, &[IND_BC, A], 1, 8, 0, "----"),
+    /* 03 */ op(Inc, &[BC], 1, 8, 0, "----"),
+    /* 04 */ op(Inc, &[B], 1, 4, 0, "Z0H-"),
+    /* 05 */ op(Dec, &[B], 1, 4, 0, "Z1H-"),
+    /* 06 */ op(Ld, &[B, D8], 2, 8, 0, "----"),
+    /* 07 */ op(Rlca, &[], 1, 4, 0, "000C"),
+    /* 08 */ op(Ld, &[IndA16, SP], 3, 20, 0, "----"),
+    /* 09 */ op(Add, &[HL, BC], 1, 8, 0, "-0HC"),
+    /* 0A */ op(Ld, &[A, IND_BC], 1, 8, 0, "----"),
+    /* 0B */ op(Dec, &[BC], 1, 8, 0, "----"),
+    /* 0C */ op(Inc, &[C], 1, 4, 0, "Z0H-"),
+    /* 0D */ op(Dec, &[C], 1, 4, 0, "Z1H-"),
+    /* 0E */ op(Ld, &[C, D8], 2, 8, 0, "----"),
+    /* 0F */ op(Rrca, &[], 1, 4, 0, "000C"),
+    /* 10 */ op(Stop, &[], 2, 4, 0, "----"),
+    /* 11 */ op(Ld, &[DE, D16], 3, 12, 0, "----"),
+    /* 12 */ op(Ld, &[IND_DE, A], 1, 8, 0, "----"),
+    /* 13 */ op(Inc, &[DE], 1, 8, 0, "----"),
+    /* 14 */ op(Inc, &[D], 1, 4, 0, "Z0H-"),
+    /* 15 */ op(Dec, &[D], 1, 4, 0, "Z1H-"),
+    /* 16 */ op(Ld, &[D, D8], 2, 8, 0, "----"),
+    /* 17 */ op(Rla, &[], 1, 4, 0, "000C"),
+    /* 18 */ op(Jr, &[R8], 2, 12, 0, "----"),
+    /* 19 */ op(Add, &[HL, DE], 1, 8, 0, "-0HC"),
+    /* 1A */ op(Ld, &[A, IND_DE], 1, 8, 0, "----"),
+    /* 1B */ op(Dec, &[DE], 1, 8, 0, "----"),
+    /* 1C */ op(Inc, &[E], 1, 4, 0, "Z0H-"),
+    /* 1D */ op(Dec, &[E], 1, 4, 0, "Z1H-"),
+    /* 1E */ op(Ld, &[E, D8], 2, 8, 0, "----"),
+    /* 1F */ op(Rra, &[], 1, 4, 0, "000C"),
+    /* 20 */ op(Jr, &[IF_NZ, R8], 2, 8, 4, "----"),
+    /* 21 */ op(Ld, &[HL, D16], 3, 12, 0, "----"),
+    /* 22 */ op(Ld, &[HL_INC, A], 1, 8, 0, "----"),
+    /* 23 */ op(Inc, &[HL], 1, 8, 0, "----"),
+    /* 24 */ op(Inc, &[H], 1, 4, 0, "Z0H-"),
+    /* 25 */ op(Dec, &[H], 1, 4, 0, "Z1H-"),
+    /* 26 */ op(Ld, &[H, D8], 2, 8, 0, "----"),
+    /* 27 */ op(Daa, &[], 1, 4, 0, "Z-0C"),
+    /* 28 */ op(Jr, &[IF_Z, R8], 2, 8, 4, "----"),
+    /* 29 */ op(Add, &[HL, HL], 1, 8, 0, "-0HC"),
+    /* 2A */ op(Ld, &[A, HL_INC], 1, 8, 0, "----"),
+    /* 2B */ op(Dec, &[HL], 1, 8, 0, "----"),
+    /* 2C */ op(Inc, &[L], 1, 4, 0, "Z0H-"),
+    /* 2D */ op(Dec, &[L], 1, 4, 0, "Z1H-"),
+    /* 2E */ op(Ld, &[L, D8], 2, 8, 0, "----"),
+    /* 2F */ op(Cpl, &[], 1, 4, 0, "-11-"),
+    /* 30 */ op(Jr, &[IF_NC, R8], 2, 8, 4, "----"),
+    /* 31 */ op(Ld, &[SP, D16], 3, 12, 0, "----"),
+    /* 32 */ op(Ld, &[HL_DEC, A], 1, 8, 0, "----"),
+    /* 33 */ op(Inc, &[SP], 1, 8, 0, "----"),
+    /* 34 */ op(Inc, &[IND_HL], 1, 12, 0, "Z0H-"),
+    /* 35 */ op(Dec, &[IND_HL], 1, 12, 0, "Z1H-"),
+    /* 36 */ op(Ld, &[IND_HL, D8], 2, 12, 0, "----"),
+    /* 37 */ op(Scf, &[], 1, 4, 0, "-001"),
+    /* 38 */ op(Jr, &[IF_C, R8], 2, 8, 4, "----"),
+    /* 39 */ op(Add, &[HL, SP], 1, 8, 0, "-0HC"),
+    /* 3A */ op(Ld, &[A, HL_DEC], 1, 8, 0, "----"),
+    /* 3B */ op(Dec, &[SP], 1, 8, 0, "----"),
+    /* 3C */ op(Inc, &[A], 1, 4, 0, "Z0H-"),
+    /* 3D */ op(Dec, &[A], 1, 4, 0, "Z1H-"),
+    /* 3E */ op(Ld, &[A, D8], 2, 8, 0, "----"),
+    /* 3F */ op(Ccf, &[], 1, 4, 0, "-00C"),
+    /* 40 */ op(Ld, &[B, B], 1, 4, 0, "----"),
+    /* 41 */ op(Ld, &[B, C], 1, 4, 0, "----"),
+    /* 42 */ op(Ld, &[B, D], 1, 4, 0, "----"),
+    /* 43 */ op(Ld, &[B, E], 1, 4, 0, "----"),
+    /* 44 */ op(Ld, &[B, H], 1, 4, 0, "----"),
+    /* 45 */ op(Ld, &[B, L], 1, 4, 0, "----"),
+    /* 46 */ op(Ld, &[B, IND_HL], 1, 8, 0, "----"),
+    /* 47 */ op(Ld, &[B, A], 1, 4, 0, "----"),
+    /* 48 */ op(Ld, &[C, B], 1, 4, 0, "----"),
+    /* 49 */ op(Ld, &[C, C], 1, 4, 0, "----"),
+    /* 4A */ op(Ld, &[C, D], 1, 4, 0, "----"),
+    /* 4B */ op(Ld, &[C, E], 1, 4, 0, "----"),
+    /* 4C */ op(Ld, &[C, H], 1, 4, 0, "----"),
+    /* 4D */ op(Ld, &[C, L], 1, 4, 0, "----"),
+    /* 4E */ op(Ld, &[C, IND_HL], 1, 8, 0, "----"),
+    /* 4F */ op(Ld, &[C, A], 1, 4, 0, "----"),
+    /* 50 */ op(Ld, &[D, B], 1, 4, 0, "----"),
+    /* 51 */ op(Ld, &[D, C], 1, 4, 0, "----"),
+    /* 52 */ op(Ld, &[D, D], 1, 4, 0, "----"),
+    /* 53 */ op(Ld, &[D, E], 1, 4, 0, "----"),
+    /* 54 */ op(Ld, &[D, H], 1, 4, 0, "----"),
+    /* 55 */ op(Ld, &[D, L], 1, 4, 0, "----"),
+    /* 56 */ op(Ld, &[D, IND_HL], 1, 8, 0, "----"),
+    /* 57 */ op(Ld, &[D, A], 1, 4, 0, "----"),
+    /* 58 */ op(Ld, &[E, B], 1, 4, 0, "----"),
+    /* 59 */ op(Ld, &[E, C], 1, 4, 0, "----"),
+    /* 5A */ op(Ld, &[E, D], 1, 4, 0, "----"),
+    /* 5B */ op(Ld, &[E, E], 1, 4, 0, "----"),
+    /* 5C */ op(Ld, &[E, H], 1, 4, 0, "----"),
+    /* 5D */ op(Ld, &[E, L], 1, 4, 0, "----"),
+    /* 5E */ op(Ld, &[E, IND_HL], 1, 8, 0, "----"),
+    /* 5F */ op(Ld, &[E, A], 1, 4, 0, "----"),
+    /* 60 */ op(Ld, &[H, B], 1, 4, 0, "----"),
+    /* 61 */ op(Ld, &[H, C], 1, 4, 0, "----"),
+    /* 62 */ op(Ld, &[H, D], 1, 4, 0, "----"),
+    /* 63 */ op(Ld, &[H, E], 1, 4, 0, "----"),
+    /* 64 */ op(Ld, &[H, H], 1, 4, 0, "----"),
+    /* 65 */ op(Ld, &[H, L], 1, 4, 0, "----"),
+    /* 66 */ op(Ld, &[H, IND_HL], 1, 8, 0, "----"),
+    /* 67 */ op(Ld, &[H, A], 1, 4, 0, "----"),
+    /* 68 */ op(Ld, &[L, B], 1, 4, 0, "----"),
+    /* 69 */ op(Ld, &[L, C], 1, 4, 0, "----"),
+    /* 6A */ op(Ld, &[L, D], 1, 4, 0, "----"),
+    /* 6B */ op(Ld, &[L, E], 1, 4, 0, "----"),
+    /* 6C */ op(Ld, &[L, H], 1, 4, 0, "----"),
+    /* 6D */ op(Ld, &[L, L], 1, 4, 0, "----"),
+    /* 6E */ op(Ld, &[L, IND_HL], 1, 8, 0, "----"),
+    /* 6F */ op(Ld, &[L, A], 1, 4, 0, "----"),
+    /* 70 */ op(Ld, &[IND_HL, B], 1, 8, 0, "----"),
+    /* 71 */ op(Ld, &[IND_HL, C], 1, 8, 0, "----"),
+    /* 72 */ op(Ld, &[IND_HL, D], 1, 8, 0, "----"),
+    /* 73 */ op(Ld, &[IND_HL, E], 1, 8, 0, "----"),
+    /* 74 */ op(Ld, &[IND_HL, H], 1, 8, 0, "----"),
+    /* 75 */ op(Ld, &[IND_HL, L], 1, 8, 0, "----"),
+    /* 76 */ op(Halt, &[], 1, 4, 0, "----"),
+    /* 77 */ op(Ld, &[IND_HL, A], 1, 8, 0, "----"),
+    /* 78 */ op(Ld, &[A, B], 1, 4, 0, "----"),
+    /* 79 */ op(Ld, &[A, C], 1, 4, 0, "----"),
+    /* 7A */ op(Ld, &[A, D], 1, 4, 0, "----"),
+    /* 7B */ op(Ld, &[A, E], 1, 4, 0, "----"),
+    /* 7C */ op(Ld, &[A, H], 1, 4, 0, "----"),
+    /* 7D */ op(Ld, &[A, L], 1, 4, 0, "----"),
+    /* 7E */ op(Ld, &[A, IND_HL], 1, 8, 0, "----"),
+    /* 7F */ op(Ld, &[A, A], 1, 4, 0, "----"),
+    /* 80 */ op(Add, &[A, B], 1, 4, 0, "Z0HC"),
+    /* 81 */ op(Add, &[A, C], 1, 4, 0, "Z0HC"),
+    /* 82 */ op(Add, &[A, D], 1, 4, 0, "Z0HC"),
+    /* 83 */ op(Add, &[A, E], 1, 4, 0, "Z0HC"),
+    /* 84 */ op(Add, &[A, H], 1, 4, 0, "Z0HC"),
+    /* 85 */ op(Add, &[A, L], 1, 4, 0, "Z0HC"),
+    /* 86 */ op(Add, &[A, IND_HL], 1, 8, 0, "Z0HC"),
+    /* 87 */ op(Add, &[A, A], 1, 4, 0, "Z0HC"),
+    /* 88 */ op(Adc, &[A, B], 1, 4, 0, "Z0HC"),
+    /* 89 */ op(Adc, &[A, C], 1, 4, 0, "Z0HC"),
+    /* 8A */ op(Adc, &[A, D], 1, 4, 0, "Z0HC"),
+    /* 8B */ op(Adc, &[A, E], 1, 4, 0, "Z0HC"),
+    /* 8C */ op(Adc, &[A, H], 1, 4, 0, "Z0HC"),
+    /* 8D */ op(Adc, &[A, L], 1, 4, 0, "Z0HC"),
+    /* 8E */ op(Adc, &[A, IND_HL], 1, 8, 0, "Z0HC"),
+    /* 8F */ op(Adc, &[A, A], 1, 4, 0, "Z0HC"),
+    /* 90 */ op(Sub, &[B], 1, 4, 0, "Z1HC"),
+    /* 91 */ op(Sub, &[C], 1, 4, 0, "Z1HC"),
+    /* 92 */ op(Sub, &[D], 1, 4, 0, "Z1HC"),
+    /* 93 */ op(Sub, &[E], 1, 4, 0, "Z1HC"),
+    /* 94 */ op(Sub, &[H], 1, 4, 0, "Z1HC"),
+    /* 95 */ op(Sub, &[L], 1, 4, 0, "Z1HC"),
+    /* 96 */ op(Sub, &[IND_HL], 1, 8, 0, "Z1HC"),
+    /* 97 */ op(Sub, &[A], 1, 4, 0, "Z1HC"),
+    /* 98 */ op(Sbc, &[A, B], 1, 4, 0, "Z1HC"),
+    /* 99 */ op(Sbc, &[A, C], 1, 4, 0, "Z1HC"),
+    /* 9A */ op(Sbc, &[A, D], 1, 4, 0, "Z1HC"),
+    /* 9B */ op(Sbc, &[A, E], 1, 4, 0, "Z1HC"),
+    /* 9C */ op(Sbc, &[A, H], 1, 4, 0, "Z1HC"),
+    /* 9D */ op(Sbc, &[A, L], 1, 4, 0, "Z1HC"),
+    /* 9E */ op(Sbc, &[A, IND_HL], 1, 8, 0, "Z1HC"),
+    /* 9F */ op(Sbc, &[A, A], 1, 4, 0, "Z1HC"),
+    /* A0 */ op(And, &[B], 1, 4, 0, "Z010"),
+    /* A1 */ op(And, &[C], 1, 4, 0, "Z010"),
+    /* A2 */ op(And, &[D], 1, 4, 0, "Z010"),
+    /* A3 */ op(And, &[E], 1, 4, 0, "Z010"),
+    /* A4 */ op(And, &[H], 1, 4, 0, "Z010"),
+    /* A5 */ op(And, &[L], 1, 4, 0, "Z010"),
+    /* A6 */ op(And, &[IND_HL], 1, 8, 0, "Z010"),
+    /* A7 */ op(And, &[A], 1, 4, 0, "Z010"),
+    /* A8 */ op(Xor, &[B], 1, 4, 0, "Z000"),
+    /* A9 */ op(Xor, &[C], 1, 4, 0, "Z000"),
+    /* AA */ op(Xor, &[D], 1, 4, 0, "Z000"),
+    /* AB */ op(Xor, &[E], 1, 4, 0, "Z000"),
+    /* AC */ op(Xor, &[H], 1, 4, 0, "Z000"),
+    /* AD */ op(Xor, &[L], 1, 4, 0, "Z000"),
+    /* AE */ op(Xor, &[IND_HL], 1, 8, 0, "Z000"),
+    /* AF */ op(Xor, &[A], 1, 4, 0, "Z000"),
+    /* B0 */ op(Or, &[B], 1, 4, 0, "Z000"),
+    /* B1 */ op(Or, &[C], 1, 4, 0, "Z000"),
+    /* B2 */ op(Or, &[D], 1, 4, 0, "Z000"),
+    /* B3 */ op(Or, &[E], 1, 4, 0, "Z000"),
+    /* B4 */ op(Or, &[H], 1, 4, 0, "Z000"),
+    /* B5 */ op(Or, &[L], 1, 4, 0, "Z000"),
+    /* B6 */ op(Or, &[IND_HL], 1, 8, 0, "Z000"),
+    /* B7 */ op(Or, &[A], 1, 4, 0, "Z000"),
+    /* B8 */ op(Cp, &[B], 1, 4, 0, "Z1HC"),
+    /* B9 */ op(Cp, &[C], 1, 4, 0, "Z1HC"),
+    /* BA */ op(Cp, &[D], 1, 4, 0, "Z1HC"),
+    /* BB */ op(Cp, &[E], 1, 4, 0, "Z1HC"),
+    /* BC */ op(Cp, &[H], 1, 4, 0, "Z1HC"),
+    /* BD */ op(Cp, &[L], 1, 4, 0, "Z1HC"),
+    /* BE */ op(Cp, &[IND_HL], 1, 8, 0, "Z1HC"),
+    /* BF */ op(Cp, &[A], 1, 4, 0, "Z1HC"),
+    /* C0 */ op(Ret, &[IF_NZ], 1, 8, 12, "----"),
+    /* C1 */ op(Pop, &[BC], 1, 12, 0, "----"),
+    /* C2 */ op(Jp, &[IF_NZ, A16], 3, 12, 4, "----"),
+    /* C3 */ op(Jp, &[A16], 3, 16, 0, "----"),
+    /* C4 */ op(Call, &[IF_NZ, A16], 3, 12, 12, "----"),
+    /* C5 */ op(Push, &[BC], 1, 16, 0, "----"),
+    /* C6 */ op(Add, &[A, D8], 2, 8, 0, "Z0HC"),
+    /* C7 */ op(Rst, &[Vector(0x00)], 1, 16, 0, "----"),
+    /* C8 */ op(Ret, &[IF_Z], 1, 8, 12, "----"),
+    /* C9 */ op(Ret, &[], 1, 16, 0, "----"),
+    /* CA */ op(Jp, &[IF_Z, A16], 3, 12, 4, "----"),
+    /* CB */ op(Prefix, &[], 1, 4, 0, "----"),
+    /* CC */ op(Call, &[IF_Z, A16], 3, 12, 12, "----"),
+    /* CD */ op(Call, &[A16], 3, 24, 0, "----"),
+    /* CE */ op(Adc, &[A, D8], 2, 8, 0, "Z0HC"),
+    /* CF */ op(Rst, &[Vector(0x08)], 1, 16, 0, "----"),
+    /* D0 */ op(Ret, &[IF_NC], 1, 8, 12, "----"),
+    /* D1 */ op(Pop, &[DE], 1, 12, 0, "----"),
+    /* D2 */ op(Jp, &[IF_NC, A16], 3, 12, 4, "----"),
+    /* D3 */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* D4 */ op(Call, &[IF_NC, A16], 3, 12, 12, "----"),
+    /* D5 */ op(Push, &[DE], 1, 16, 0, "----"),
+    /* D6 */ op(Sub, &[D8], 2, 8, 0, "Z1HC"),
+    /* D7 */ op(Rst, &[Vector(0x10)], 1, 16, 0, "----"),
+    /* D8 */ op(Ret, &[IF_C], 1, 8, 12, "----"),
+    /* D9 */ op(Reti, &[], 1, 16, 0, "----"),
+    /* DA */ op(Jp, &[IF_C, A16], 3, 12, 4, "----"),
+    /* DB */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* DC */ op(Call, &[IF_C, A16], 3, 12, 12, "----"),
+    /* DD */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* DE */ op(Sbc, &[A, D8], 2, 8, 0, "Z1HC"),
+    /* DF */ op(Rst, &[Vector(0x18)], 1, 16, 0, "----"),
+    /* E0 */ op(Ldh, &[PagedA8, A], 2, 12, 0, "----"),
+    /* E1 */ op(Pop, &[HL], 1, 12, 0, "----"),
+    /* E2 */ op(Ld, &[PagedC, A], 1, 8, 0, "----"),
+    /* E3 */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* E4 */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* E5 */ op(Push, &[HL], 1, 16, 0, "----"),
+    /* E6 */ op(And, &[D8], 2, 8, 0, "Z010"),
+    /* E7 */ op(Rst, &[Vector(0x20)], 1, 16, 0, "----"),
+    /* E8 */ op(Add, &[SP, R8], 2, 16, 0, "00HC"),
+    /* E9 */ op(Jp, &[HL], 1, 4, 0, "----"),
+    /* EA */ op(Ld, &[IndA16, A], 3, 16, 0, "----"),
+    /* EB */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* EC */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* ED */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* EE */ op(Xor, &[D8], 2, 8, 0, "Z000"),
+    /* EF */ op(Rst, &[Vector(0x28)], 1, 16, 0, "----"),
+    /* F0 */ op(Ldh, &[A, PagedA8], 2, 12, 0, "----"),
+    /* F1 */ op(Pop, &[AF], 1, 12, 0, "ZNHC"),
+    /* F2 */ op(Ld, &[A, PagedC], 1, 8, 0, "----"),
+    /* F3 */ op(Di, &[], 1, 4, 0, "----"),
+    /* F4 */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* F5 */ op(Push, &[AF], 1, 16, 0, "----"),
+    /* F6 */ op(Or, &[D8], 2, 8, 0, "Z000"),
+    /* F7 */ op(Rst, &[Vector(0x30)], 1, 16, 0, "----"),
+    /* F8 */ op(Ld, &[HL, SPOffset], 2, 12, 0, "00HC"),
+    /* F9 */ op(Ld, &[SP, HL], 1, 8, 0, "----"),
+    /* FA */ op(Ld, &[A, IndA16], 3, 16, 0, "----"),
+    /* FB */ op(Ei, &[], 1, 4, 0, "----"),
+    /* FC */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* FD */ op(Illegal, &[], 1, 4, 0, "----"),
+    /* FE */ op(Cp, &[D8], 2, 8, 0, "Z1HC"),
+    /* FF */ op(Rst, &[Vector(0x38)], 1, 16, 0, "----"),
+];
+
+pub static CB_OPCODES: [Opcode; 256] = [
+    /* 00 */ op(Rlc, &[B], 2, 8, 0, "Z00C"),
+    /* 01 */ op(Rlc, &[C], 2, 8, 0, "Z00C"),
+    /* 02 */ op(Rlc, &[D], 2, 8, 0, "Z00C"),
+    /* 03 */ op(Rlc, &[E], 2, 8, 0, "Z00C"),
+    /* 04 */ op(Rlc, &[H], 2, 8, 0, "Z00C"),
+    /* 05 */ op(Rlc, &[L], 2, 8, 0, "Z00C"),
+    /* 06 */ op(Rlc, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 07 */ op(Rlc, &[A], 2, 8, 0, "Z00C"),
+    /* 08 */ op(Rrc, &[B], 2, 8, 0, "Z00C"),
+    /* 09 */ op(Rrc, &[C], 2, 8, 0, "Z00C"),
+    /* 0A */ op(Rrc, &[D], 2, 8, 0, "Z00C"),
+    /* 0B */ op(Rrc, &[E], 2, 8, 0, "Z00C"),
+    /* 0C */ op(Rrc, &[H], 2, 8, 0, "Z00C"),
+    /* 0D */ op(Rrc, &[L], 2, 8, 0, "Z00C"),
+    /* 0E */ op(Rrc, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 0F */ op(Rrc, &[A], 2, 8, 0, "Z00C"),
+    /* 10 */ op(Rl, &[B], 2, 8, 0, "Z00C"),
+    /* 11 */ op(Rl, &[C], 2, 8, 0, "Z00C"),
+    /* 12 */ op(Rl, &[D], 2, 8, 0, "Z00C"),
+    /* 13 */ op(Rl, &[E], 2, 8, 0, "Z00C"),
+    /* 14 */ op(Rl, &[H], 2, 8, 0, "Z00C"),
+    /* 15 */ op(Rl, &[L], 2, 8, 0, "Z00C"),
+    /* 16 */ op(Rl, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 17 */ op(Rl, &[A], 2, 8, 0, "Z00C"),
+    /* 18 */ op(Rr, &[B], 2, 8, 0, "Z00C"),
+    /* 19 */ op(Rr, &[C], 2, 8, 0, "Z00C"),
+    /* 1A */ op(Rr, &[D], 2, 8, 0, "Z00C"),
+    /* 1B */ op(Rr, &[E], 2, 8, 0, "Z00C"),
+    /* 1C */ op(Rr, &[H], 2, 8, 0, "Z00C"),
+    /* 1D */ op(Rr, &[L], 2, 8, 0, "Z00C"),
+    /* 1E */ op(Rr, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 1F */ op(Rr, &[A], 2, 8, 0, "Z00C"),
+    /* 20 */ op(Sla, &[B], 2, 8, 0, "Z00C"),
+    /* 21 */ op(Sla, &[C], 2, 8, 0, "Z00C"),
+    /* 22 */ op(Sla, &[D], 2, 8, 0, "Z00C"),
+    /* 23 */ op(Sla, &[E], 2, 8, 0, "Z00C"),
+    /* 24 */ op(Sla, &[H], 2, 8, 0, "Z00C"),
+    /* 25 */ op(Sla, &[L], 2, 8, 0, "Z00C"),
+    /* 26 */ op(Sla, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 27 */ op(Sla, &[A], 2, 8, 0, "Z00C"),
+    /* 28 */ op(Sra, &[B], 2, 8, 0, "Z00C"),
+    /* 29 */ op(Sra, &[C], 2, 8, 0, "Z00C"),
+    /* 2A */ op(Sra, &[D], 2, 8, 0, "Z00C"),
+    /* 2B */ op(Sra, &[E], 2, 8, 0, "Z00C"),
+    /* 2C */ op(Sra, &[H], 2, 8, 0, "Z00C"),
+    /* 2D */ op(Sra, &[L], 2, 8, 0, "Z00C"),
+    /* 2E */ op(Sra, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 2F */ op(Sra, &[A], 2, 8, 0, "Z00C"),
+    /* 30 */ op(Swap, &[B], 2, 8, 0, "Z000"),
+    /* 31 */ op(Swap, &[C], 2, 8, 0, "Z000"),
+    /* 32 */ op(Swap, &[D], 2, 8, 0, "Z000"),
+    /* 33 */ op(Swap, &[E], 2, 8, 0, "Z000"),
+    /* 34 */ op(Swap, &[H], 2, 8, 0, "Z000"),
+    /* 35 */ op(Swap, &[L], 2, 8, 0, "Z000"),
+    /* 36 */ op(Swap, &[IND_HL], 2, 16, 0, "Z000"),
+    /* 37 */ op(Swap, &[A], 2, 8, 0, "Z000"),
+    /* 38 */ op(Srl, &[B], 2, 8, 0, "Z00C"),
+    /* 39 */ op(Srl, &[C], 2, 8, 0, "Z00C"),
+    /* 3A */ op(Srl, &[D], 2, 8, 0, "Z00C"),
+    /* 3B */ op(Srl, &[E], 2, 8, 0, "Z00C"),
+    /* 3C */ op(Srl, &[H], 2, 8, 0, "Z00C"),
+    /* 3D */ op(Srl, &[L], 2, 8, 0, "Z00C"),
+    /* 3E */ op(Srl, &[IND_HL], 2, 16, 0, "Z00C"),
+    /* 3F */ op(Srl, &[A], 2, 8, 0, "Z00C"),
+    /* 40 */ op(Bit, &[BitIndex(0), B], 2, 8, 0, "Z01-"),
+    /* 41 */ op(Bit, &[BitIndex(0), C], 2, 8, 0, "Z01-"),
+    /* 42 */ op(Bit, &[BitIndex(0), D], 2, 8, 0, "Z01-"),
+    /* 43 */ op(Bit, &[BitIndex(0), E], 2, 8, 0, "Z01-"),
+    /* 44 */ op(Bit, &[BitIndex(0), H], 2, 8, 0, "Z01-"),
+    /* 45 */ op(Bit, &[BitIndex(0), L], 2, 8, 0, "Z01-"),
+    /* 46 */ op(Bit, &[BitIndex(0), IND_HL], 2, 12, 0, "Z01-"),
+    /* 47 */ op(Bit, &[BitIndex(0), A], 2, 8, 0, "Z01-"),
+    /* 48 */ op(Bit, &[BitIndex(1), B], 2, 8, 0, "Z01-"),
+    /* 49 */ op(Bit, &[BitIndex(1), C], 2, 8, 0, "Z01-"),
+    /* 4A */ op(Bit, &[BitIndex(1), D], 2, 8, 0, "Z01-"),
+    /* 4B */ op(Bit, &[BitIndex(1), E], 2, 8, 0, "Z01-"),
+    /* 4C */ op(Bit, &[BitIndex(1), H], 2, 8, 0, "Z01-"),
+    /* 4D */ op(Bit, &[BitIndex(1), L], 2, 8, 0, "Z01-"),
+    /* 4E */ op(Bit, &[BitIndex(1), IND_HL], 2, 12, 0, "Z01-"),
+    /* 4F */ op(Bit, &[BitIndex(1), A], 2, 8, 0, "Z01-"),
+    /* 50 */ op(Bit, &[BitIndex(2), B], 2, 8, 0, "Z01-"),
+    /* 51 */ op(Bit, &[BitIndex(2), C], 2, 8, 0, "Z01-"),
+    /* 52 */ op(Bit, &[BitIndex(2), D], 2, 8, 0, "Z01-"),
+    /* 53 */ op(Bit, &[BitIndex(2), E], 2, 8, 0, "Z01-"),
+    /* 54 */ op(Bit, &[BitIndex(2), H], 2, 8, 0, "Z01-"),
+    /* 55 */ op(Bit, &[BitIndex(2), L], 2, 8, 0, "Z01-"),
+    /* 56 */ op(Bit, &[BitIndex(2), IND_HL], 2, 12, 0, "Z01-"),
+    /* 57 */ op(Bit, &[BitIndex(2), A], 2, 8, 0, "Z01-"),
+    /* 58 */ op(Bit, &[BitIndex(3), B], 2, 8, 0, "Z01-"),
+    /* 59 */ op(Bit, &[BitIndex(3), C], 2, 8, 0, "Z01-"),
+    /* 5A */ op(Bit, &[BitIndex(3), D], 2, 8, 0, "Z01-"),
+    /* 5B */ op(Bit, &[BitIndex(3), E], 2, 8, 0, "Z01-"),
+    /* 5C */ op(Bit, &[BitIndex(3), H], 2, 8, 0, "Z01-"),
+    /* 5D */ op(Bit, &[BitIndex(3), L], 2, 8, 0, "Z01-"),
+    /* 5E */ op(Bit, &[BitIndex(3), IND_HL], 2, 12, 0, "Z01-"),
+    /* 5F */ op(Bit, &[BitIndex(3), A], 2, 8, 0, "Z01-"),
+    /* 60 */ op(Bit, &[BitIndex(4), B], 2, 8, 0, "Z01-"),
+    /* 61 */ op(Bit, &[BitIndex(4), C], 2, 8, 0, "Z01-"),
+    /* 62 */ op(Bit, &[BitIndex(4), D], 2, 8, 0, "Z01-"),
+    /* 63 */ op(Bit, &[BitIndex(4), E], 2, 8, 0, "Z01-"),
+    /* 64 */ op(Bit, &[BitIndex(4), H], 2, 8, 0, "Z01-"),
+    /* 65 */ op(Bit, &[BitIndex(4), L], 2, 8, 0, "Z01-"),
+    /* 66 */ op(Bit, &[BitIndex(4), IND_HL], 2, 12, 0, "Z01-"),
+    /* 67 */ op(Bit, &[BitIndex(4), A], 2, 8, 0, "Z01-"),
+    /* 68 */ op(Bit, &[BitIndex(5), B], 2, 8, 0, "Z01-"),
+    /* 69 */ op(Bit, &[BitIndex(5), C], 2, 8, 0, "Z01-"),
+    /* 6A */ op(Bit, &[BitIndex(5), D], 2, 8, 0, "Z01-"),
+    /* 6B */ op(Bit, &[BitIndex(5), E], 2, 8, 0, "Z01-"),
+    /* 6C */ op(Bit, &[BitIndex(5), H], 2, 8, 0, "Z01-"),
+    /* 6D */ op(Bit, &[BitIndex(5), L], 2, 8, 0, "Z01-"),
+    /* 6E */ op(Bit, &[BitIndex(5), IND_HL], 2, 12, 0, "Z01-"),
+    /* 6F */ op(Bit, &[BitIndex(5), A], 2, 8, 0, "Z01-"),
+    /* 70 */ op(Bit, &[BitIndex(6), B], 2, 8, 0, "Z01-"),
+    /* 71 */ op(Bit, &[BitIndex(6), C], 2, 8, 0, "Z01-"),
+    /* 72 */ op(Bit, &[BitIndex(6), D], 2, 8, 0, "Z01-"),
+    /* 73 */ op(Bit, &[BitIndex(6), E], 2, 8, 0, "Z01-"),
+    /* 74 */ op(Bit, &[BitIndex(6), H], 2, 8, 0, "Z01-"),
+    /* 75 */ op(Bit, &[BitIndex(6), L], 2, 8, 0, "Z01-"),
+    /* 76 */ op(Bit, &[BitIndex(6), IND_HL], 2, 12, 0, "Z01-"),
+    /* 77 */ op(Bit, &[BitIndex(6), A], 2, 8, 0, "Z01-"),
+    /* 78 */ op(Bit, &[BitIndex(7), B], 2, 8, 0, "Z01-"),
+    /* 79 */ op(Bit, &[BitIndex(7), C], 2, 8, 0, "Z01-"),
+    /* 7A */ op(Bit, &[BitIndex(7), D], 2, 8, 0, "Z01-"),
+    /* 7B */ op(Bit, &[BitIndex(7), E], 2, 8, 0, "Z01-"),
+    /* 7C */ op(Bit, &[BitIndex(7), H], 2, 8, 0, "Z01-"),
+    /* 7D */ op(Bit, &[BitIndex(7), L], 2, 8, 0, "Z01-"),
+    /* 7E */ op(Bit, &[BitIndex(7), IND_HL], 2, 12, 0, "Z01-"),
+    /* 7F */ op(Bit, &[BitIndex(7), A], 2, 8, 0, "Z01-"),
+    /* 80 */ op(Res, &[BitIndex(0), B], 2, 8, 0, "----"),
+    /* 81 */ op(Res, &[BitIndex(0), C], 2, 8, 0, "----"),
+    /* 82 */ op(Res, &[BitIndex(0), D], 2, 8, 0, "----"),
+    /* 83 */ op(Res, &[BitIndex(0), E], 2, 8, 0, "----"),
+    /* 84 */ op(Res, &[BitIndex(0), H], 2, 8, 0, "----"),
+    /* 85 */ op(Res, &[BitIndex(0), L], 2, 8, 0, "----"),
+    /* 86 */ op(Res, &[BitIndex(0), IND_HL], 2, 16, 0, "----"),
+    /* 87 */ op(Res, &[BitIndex(0), A], 2, 8, 0, "----"),
+    /* 88 */ op(Res, &[BitIndex(1), B], 2, 8, 0, "----"),
+    /* 89 */ op(Res, &[BitIndex(1), C], 2, 8, 0, "----"),
+    /* 8A */ op(Res, &[BitIndex(1), D], 2, 8, 0, "----"),
+    /* 8B */ op(Res, &[BitIndex(1), E], 2, 8, 0, "----"),
+    /* 8C */ op(Res, &[BitIndex(1), H], 2, 8, 0, "----"),
+    /* 8D */ op(Res, &[BitIndex(1), L], 2, 8, 0, "----"),
+    /* 8E */ op(Res, &[BitIndex(1), IND_HL], 2, 16, 0, "----"),
+    /* 8F */ op(Res, &[BitIndex(1), A], 2, 8, 0, "----"),
+    /* 90 */ op(Res, &[BitIndex(2), B], 2, 8, 0, "----"),
+    /* 91 */ op(Res, &[BitIndex(2), C], 2, 8, 0, "----"),
+    /* 92 */ op(Res, &[BitIndex(2), D], 2, 8, 0, "----"),
+    /* 93 */ op(Res, &[BitIndex(2), E], 2, 8, 0, "----"),
+    /* 94 */ op(Res, &[BitIndex(2), H], 2, 8, 0, "----"),
+    /* 95 */ op(Res, &[BitIndex(2), L], 2, 8, 0, "----"),
+    /* 96 */ op(Res, &[BitIndex(2), IND_HL], 2, 16, 0, "----"),
+    /* 97 */ op(Res, &[BitIndex(2), A], 2, 8, 0, "----"),
+    /* 98 */ op(Res, &[BitIndex(3), B], 2, 8, 0, "----"),
+    /* 99 */ op(Res, &[BitIndex(3), C], 2, 8, 0, "----"),
+    /* 9A */ op(Res, &[BitIndex(3), D], 2, 8, 0, "----"),
+    /* 9B */ op(Res, &[BitIndex(3), E], 2, 8, 0, "----"),
+    /* 9C */ op(Res, &[BitIndex(3), H], 2, 8, 0, "----"),
+    /* 9D */ op(Res, &[BitIndex(3), L], 2, 8, 0, "----"),
+    /* 9E */ op(Res, &[BitIndex(3), IND_HL], 2, 16, 0, "----"),
+    /* 9F */ op(Res, &[BitIndex(3), A], 2, 8, 0, "----"),
+    /* A0 */ op(Res, &[BitIndex(4), B], 2, 8, 0, "----"),
+    /* A1 */ op(Res, &[BitIndex(4), C], 2, 8, 0, "----"),
+    /* A2 */ op(Res, &[BitIndex(4), D], 2, 8, 0, "----"),
+    /* A3 */ op(Res, &[BitIndex(4), E], 2, 8, 0, "----"),
+    /* A4 */ op(Res, &[BitIndex(4), H], 2, 8, 0, "----"),
+    /* A5 */ op(Res, &[BitIndex(4), L], 2, 8, 0, "----"),
+    /* A6 */ op(Res, &[BitIndex(4), IND_HL], 2, 16, 0, "----"),
+    /* A7 */ op(Res, &[BitIndex(4), A], 2, 8, 0, "----"),
+    /* A8 */ op(Res, &[BitIndex(5), B], 2, 8, 0, "----"),
+    /* A9 */ op(Res, &[BitIndex(5), C], 2, 8, 0, "----"),
+    /* AA */ op(Res, &[BitIndex(5), D], 2, 8, 0, "----"),
+    /* AB */ op(Res, &[BitIndex(5), E], 2, 8, 0, "----"),
+    /* AC */ op(Res, &[BitIndex(5), H], 2, 8, 0, "----"),
+    /* AD */ op(Res, &[BitIndex(5), L], 2, 8, 0, "----"),
+    /* AE */ op(Res, &[BitIndex(5), IND_HL], 2, 16, 0, "----"),
+    /* AF */ op(Res, &[BitIndex(5), A], 2, 8, 0, "----"),
+    /* B0 */ op(Res, &[BitIndex(6), B], 2, 8, 0, "----"),
+    /* B1 */ op(Res, &[BitIndex(6), C], 2, 8, 0, "----"),
+    /* B2 */ op(Res, &[BitIndex(6), D], 2, 8, 0, "----"),
+    /* B3 */ op(Res, &[BitIndex(6), E], 2, 8, 0, "----"),
+    /* B4 */ op(Res, &[BitIndex(6), H], 2, 8, 0, "----"),
+    /* B5 */ op(Res, &[BitIndex(6), L], 2, 8, 0, "----"),
+    /* B6 */ op(Res, &[BitIndex(6), IND_HL], 2, 16, 0, "----"),
+    /* B7 */ op(Res, &[BitIndex(6), A], 2, 8, 0, "----"),
+    /* B8 */ op(Res, &[BitIndex(7), B], 2, 8, 0, "----"),
+    /* B9 */ op(Res, &[BitIndex(7), C], 2, 8, 0, "----"),
+    /* BA */ op(Res, &[BitIndex(7), D], 2, 8, 0, "----"),
+    /* BB */ op(Res, &[BitIndex(7), E], 2, 8, 0, "----"),
+    /* BC */ op(Res, &[BitIndex(7), H], 2, 8, 0, "----"),
+    /* BD */ op(Res, &[BitIndex(7), L], 2, 8, 0, "----"),
+    /* BE */ op(Res, &[BitIndex(7), IND_HL], 2, 16, 0, "----"),
+    /* BF */ op(Res, &[BitIndex(7), A], 2, 8, 0, "----"),
+    /* C0 */ op(Set, &[BitIndex(0), B], 2, 8, 0, "----"),
+    /* C1 */ op(Set, &[BitIndex(0), C], 2, 8, 0, "----"),
+    /* C2 */ op(Set, &[BitIndex(0), D], 2, 8, 0, "----"),
+    /* C3 */ op(Set, &[BitIndex(0), E], 2, 8, 0, "----"),
+    /* C4 */ op(Set, &[BitIndex(0), H], 2, 8, 0, "----"),
+    /* C5 */ op(Set, &[BitIndex(0), L], 2, 8, 0, "----"),
+    /* C6 */ op(Set, &[BitIndex(0), IND_HL], 2, 16, 0, "----"),
+    /* C7 */ op(Set, &[BitIndex(0), A], 2, 8, 0, "----"),
+    /* C8 */ op(Set, &[BitIndex(1), B], 2, 8, 0, "----"),
+    /* C9 */ op(Set, &[BitIndex(1), C], 2, 8, 0, "----"),
+    /* CA */ op(Set, &[BitIndex(1), D], 2, 8, 0, "----"),
+    /* CB */ op(Set, &[BitIndex(1), E], 2, 8, 0, "----"),
+    /* CC */ op(Set, &[BitIndex(1), H], 2, 8, 0, "----"),
+    /* CD */ op(Set, &[BitIndex(1), L], 2, 8, 0, "----"),
+    /* CE */ op(Set, &[BitIndex(1), IND_HL], 2, 16, 0, "----"),
+    /* CF */ op(Set, &[BitIndex(1), A], 2, 8, 0, "----"),
+    /* D0 */ op(Set, &[BitIndex(2), B], 2, 8, 0, "----"),
+    /* D1 */ op(Set, &[BitIndex(2), C], 2, 8, 0, "----"),
+    /* D2 */ op(Set, &[BitIndex(2), D], 2, 8, 0, "----"),
+    /* D3 */ op(Set, &[BitIndex(2), E], 2, 8, 0, "----"),
+    /* D4 */ op(Set, &[BitIndex(2), H], 2, 8, 0, "----"),
+    /* D5 */ op(Set, &[BitIndex(2), L], 2, 8, 0, "----"),
+    /* D6 */ op(Set, &[BitIndex(2), IND_HL], 2, 16, 0, "----"),
+    /* D7 */ op(Set, &[BitIndex(2), A], 2, 8, 0, "----"),
+    /* D8 */ op(Set, &[BitIndex(3), B], 2, 8, 0, "----"),
+    /* D9 */ op(Set, &[BitIndex(3), C], 2, 8, 0, "----"),
+    /* DA */ op(Set, &[BitIndex(3), D], 2, 8, 0, "----"),
+    /* DB */ op(Set, &[BitIndex(3), E], 2, 8, 0, "----"),
+    /* DC */ op(Set, &[BitIndex(3), H], 2, 8, 0, "----"),
+    /* DD */ op(Set, &[BitIndex(3), L], 2, 8, 0, "----"),
+    /* DE */ op(Set, &[BitIndex(3), IND_HL], 2, 16, 0, "----"),
+    /* DF */ op(Set, &[BitIndex(3), A], 2, 8, 0, "----"),
+    /* E0 */ op(Set, &[BitIndex(4), B], 2, 8, 0, "----"),
+    /* E1 */ op(Set, &[BitIndex(4), C], 2, 8, 0, "----"),
+    /* E2 */ op(Set, &[BitIndex(4), D], 2, 8, 0, "----"),
+    /* E3 */ op(Set, &[BitIndex(4), E], 2, 8, 0, "----"),
+    /* E4 */ op(Set, &[BitIndex(4), H], 2, 8, 0, "----"),
+    /* E5 */ op(Set, &[BitIndex(4), L], 2, 8, 0, "----"),
+    /* E6 */ op(Set, &[BitIndex(4), IND_HL], 2, 16, 0, "----"),
+    /* E7 */ op(Set, &[BitIndex(4), A], 2, 8, 0, "----"),
+    /* E8 */ op(Set, &[BitIndex(5), B], 2, 8, 0, "----"),
+    /* E9 */ op(Set, &[BitIndex(5), C], 2, 8, 0, "----"),
+    /* EA */ op(Set, &[BitIndex(5), D], 2, 8, 0, "----"),
+    /* EB */ op(Set, &[BitIndex(5), E], 2, 8, 0, "----"),
+    /* EC */ op(Set, &[BitIndex(5), H], 2, 8, 0, "----"),
+    /* ED */ op(Set, &[BitIndex(5), L], 2, 8, 0, "----"),
+    /* EE */ op(Set, &[BitIndex(5), IND_HL], 2, 16, 0, "----"),
+    /* EF */ op(Set, &[BitIndex(5), A], 2, 8, 0, "----"),
+    /* F0 */ op(Set, &[BitIndex(6), B], 2, 8, 0, "----"),
+    /* F1 */ op(Set, &[BitIndex(6), C], 2, 8, 0, "----"),
+    /* F2 */ op(Set, &[BitIndex(6), D], 2, 8, 0, "----"),
+    /* F3 */ op(Set, &[BitIndex(6), E], 2, 8, 0, "----"),
+    /* F4 */ op(Set, &[BitIndex(6), H], 2, 8, 0, "----"),
+    /* F5 */ op(Set, &[BitIndex(6), L], 2, 8, 0, "----"),
+    /* F6 */ op(Set, &[BitIndex(6), IND_HL], 2, 16, 0, "----"),
+    /* F7 */ op(Set, &[BitIndex(6), A], 2, 8, 0, "----"),
+    /* F8 */ op(Set, &[BitIndex(7), B], 2, 8, 0, "----"),
+    /* F9 */ op(Set, &[BitIndex(7), C], 2, 8, 0, "----"),
+    /* FA */ op(Set, &[BitIndex(7), D], 2, 8, 0, "----"),
+    /* FB */ op(Set, &[BitIndex(7), E], 2, 8, 0, "----"),
+    /* FC */ op(Set, &[BitIndex(7), H], 2, 8, 0, "----"),
+    /* FD */ op(Set, &[BitIndex(7), L], 2, 8, 0, "----"),
+    /* FE */ op(Set, &[BitIndex(7), IND_HL], 2, 16, 0, "----"),
+    /* FF */ op(Set, &[BitIndex(7), A], 2, 8, 0, "----"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths_match_operands() {
+        for (opcode, spec) in OPCODES.iter().enumerate() {
+            // STOP carries a padding byte that isn't an operand
+            let padding = if spec.mnemonic == Stop { 1 } else { 0 };
+            assert_eq!(spec.byte_length, 1 + spec.immediate_len() + padding, "opcode {:02X}", opcode);
+        }
+
+        for (opcode, spec) in CB_OPCODES.iter().enumerate() {
+            assert_eq!(spec.byte_length, 2, "opcode CB {:02X}", opcode);
+            assert_eq!(spec.immediate_len(), 0, "opcode CB {:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn only_conditional_branches_have_a_penalty() {
+        for (opcode, spec) in OPCODES.iter().chain(CB_OPCODES.iter()).enumerate() {
+            let conditional = spec.operands.iter().any(|operand| matches!(*operand, Cond(_)));
+
+            assert_eq!(spec.branch_penalty > 0, conditional, "entry {:03X}", opcode);
+        }
+    }
+
+    #[test]
+    fn flag_effects_are_well_formed() {
+        for (opcode, spec) in OPCODES.iter().chain(CB_OPCODES.iter()).enumerate() {
+            assert_eq!(spec.flags.len(), 4, "entry {:03X}", opcode);
+
+            for (effect, name) in spec.flags.chars().zip("ZNHC".chars()) {
+                assert!(
+                    effect == name || effect == '0' || effect == '1' || effect == '-',
+                    "entry {:03X}: {}",
+                    opcode,
+                    spec.flags
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cycles_match_pan_docs_samples() {
+        let samples = [
+            (0x00, 4),
+            (0x08, 20),
+            (0x36, 12),
+            (0x76, 4),
+            (0xC3, 16),
+            (0xCD, 24),
+            (0xE8, 16),
+            (0xF8, 12),
+        ];
+
+        for &(opcode, cycles) in samples.iter() {
+            assert_eq!(OPCODES[opcode].cycle_duration, cycles, "opcode {:02X}", opcode);
+        }
+
+        assert_eq!(CB_OPCODES[0x46].cycle_duration, 12);
+        assert_eq!(CB_OPCODES[0x86].cycle_duration, 16);
+        assert_eq!(CB_OPCODES[0x37].cycle_duration, 8);
+    }
+}
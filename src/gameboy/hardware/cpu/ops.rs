@@ -1,22 +1,58 @@
-use super::instructions::{Dst, Instruction, JumpCondition, Src};
-use super::registers::{Reg16, Reg8};
+use super::instructions::{Dst, JumpCondition, Loc8, Src};
+use super::registers::Reg16;
+use super::super::error::Result;
+use super::super::interrupts::Interrupt;
 
 pub trait Ops {
-    fn nop(self);
-    fn bit(self, bit: usize, reg: Reg8);
-    fn dec(self, reg: Reg8);
-    fn inc(self, reg: Reg8);
-    fn load(self, dst: Dst, src: Src);
-    fn xor(self, reg: Reg8);
-    fn call(self, addr: u16);
-    fn jr_c(self, cond: JumpCondition, offset: i8);
-    fn jr(self, offset: i8);
-    fn ret(self, addr: u16);
-    fn push16(self, reg: Reg16);
-    fn pop16(self, reg: Reg16);
-    fn rl(self, reg: Reg8, set_zero: bool);
-    fn inc16(self, reg: Reg16);
-    fn sub(self, val: u8);
-
-    fn prefix_cb(self) -> Instruction;
+    fn nop(self) -> Result<()>;
+    fn stop(self) -> Result<()>;
+    fn halt(self) -> Result<()>;
+    fn lock(self) -> Result<()>;
+    fn di(self) -> Result<()>;
+    fn ei(self) -> Result<()>;
+    fn isr(self, irq: Interrupt) -> Result<()>;
+    fn bit(self, bit: usize, loc: Loc8) -> Result<()>;
+    fn res(self, bit: usize, loc: Loc8) -> Result<()>;
+    fn set(self, bit: usize, loc: Loc8) -> Result<()>;
+    fn dec(self, loc: Loc8) -> Result<()>;
+    fn inc(self, loc: Loc8) -> Result<()>;
+    fn load(self, dst: Dst, src: Src) -> Result<()>;
+    fn load_hl_sp(self, offset: i8) -> Result<()>;
+    fn add(self, src: Src) -> Result<()>;
+    fn adc(self, src: Src) -> Result<()>;
+    fn sub(self, src: Src) -> Result<()>;
+    fn sbc(self, src: Src) -> Result<()>;
+    fn and(self, src: Src) -> Result<()>;
+    fn xor(self, src: Src) -> Result<()>;
+    fn or(self, src: Src) -> Result<()>;
+    fn cp(self, src: Src) -> Result<()>;
+    fn add_hl(self, reg: Reg16) -> Result<()>;
+    fn add_sp(self, offset: i8) -> Result<()>;
+    fn call(self, addr: u16) -> Result<()>;
+    fn call_c(self, cond: JumpCondition, addr: u16) -> Result<bool>;
+    fn jr_c(self, cond: JumpCondition, offset: i8) -> Result<bool>;
+    fn jr(self, offset: i8) -> Result<()>;
+    fn jp(self, addr: u16) -> Result<()>;
+    fn jp_c(self, cond: JumpCondition, addr: u16) -> Result<bool>;
+    fn jp_hl(self) -> Result<()>;
+    fn ret(self) -> Result<()>;
+    fn ret_c(self, cond: JumpCondition) -> Result<bool>;
+    fn reti(self) -> Result<()>;
+    fn rst(self, addr: u8) -> Result<()>;
+    fn push16(self, reg: Reg16) -> Result<()>;
+    fn pop16(self, reg: Reg16) -> Result<()>;
+    fn rl(self, loc: Loc8, set_zero: bool) -> Result<()>;
+    fn rlc(self, loc: Loc8, set_zero: bool) -> Result<()>;
+    fn rr(self, loc: Loc8, set_zero: bool) -> Result<()>;
+    fn rrc(self, loc: Loc8, set_zero: bool) -> Result<()>;
+    fn sla(self, loc: Loc8) -> Result<()>;
+    fn sra(self, loc: Loc8) -> Result<()>;
+    fn srl(self, loc: Loc8) -> Result<()>;
+    fn swap(self, loc: Loc8) -> Result<()>;
+    fn daa(self) -> Result<()>;
+    fn cpl(self) -> Result<()>;
+    fn scf(self) -> Result<()>;
+    fn ccf(self) -> Result<()>;
+    fn inc16(self, reg: Reg16) -> Result<()>;
+    fn dec16(self, reg: Reg16) -> Result<()>;
 }
@@ -1,5 +1,7 @@
 use std::fmt;
 
+// Only the upper nibble of F exists in hardware, and since `Flags` can only
+// hold the bits declared here, the lower nibble always reads back as zero.
 bitflags!(
   pub struct Flags: u8 {
     const ZERO       = 0b_1000_0000; 
@@ -117,7 +119,10 @@ impl Registers {
         use self::Reg16::*;
 
         match reg {
-            AF => unimplemented!(),
+            AF => {
+                self.a = (val >> 8) as u8;
+                self.f = Flags::from_bits_truncate(val as u8);
+            }
             BC => {
                 (self.b = (val >> 8) as u8);
                 self.c = val as u8;
@@ -145,3 +150,26 @@ impl fmt::Debug for Registers {
         write!(f, "+--------+-------+-------+-------+-------+-------+-------+-------+-------+-------+\n")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write16_af_masks_low_nibble() {
+        let mut registers = Registers::default();
+
+        registers.write16(Reg16::AF, 0x12FF);
+
+        assert_eq!(registers.read8(Reg8::A), 0x12);
+        assert_eq!(registers.f.bits(), 0xF0);
+        assert_eq!(registers.read16(Reg16::AF), 0x12F0);
+    }
+
+    #[test]
+    fn flags_never_hold_low_nibble() {
+        assert_eq!(Flags::from_bits_truncate(0x0F), Flags::empty());
+        assert_eq!((!Flags::empty()).bits(), 0xF0);
+        assert_eq!(Flags::from_bits(0x8F), None);
+    }
+}
@@ -0,0 +1,28 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+/// A fault raised while emulating. The machine is left in a consistent
+/// state, so frontends can report it and keep the session going.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmuError {
+    /// One of the unused opcodes, which hangs the CPU
+    IllegalOpcode { opcode: u8, addr: u16 },
+    /// The cartridge couldn't serve an access, e.g. past the end of its ROM
+    CartridgeFault { addr: u16, reason: &'static str },
+}
+
+pub type Result<T> = result::Result<T, EmuError>;
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::EmuError::*;
+
+        match *self {
+            IllegalOpcode { opcode, addr } => write!(f, "illegal opcode ${:02X} at ${:04X}", opcode, addr),
+            CartridgeFault { addr, reason } => write!(f, "cartridge fault at ${:04X}: {}", addr, reason),
+        }
+    }
+}
+
+impl error::Error for EmuError {}
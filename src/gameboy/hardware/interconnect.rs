@@ -1,76 +1,339 @@
-use failure::Error;
-
+use super::apu::{Apu, NR10_ADDR, WAVE_END};
 use super::bus::MemoryBus;
-use super::cartridge::Cartridge;
+use super::cartridge::{Cartridge, HeaderError};
+use super::error::Result;
+use super::interrupts::{InterruptController, IE_ADDR, IF_ADDR};
+use super::model::Model;
 use super::ppu::PPU;
+use super::timer::{Timer, DIV_ADDR, TAC_ADDR};
+
+const KEY1_ADDR: u16 = 0xFF4D;
+/// Writing anything but zero here unmaps the boot ROM until the next reset
+const BOOT_ADDR: u16 = 0xFF50;
+
+/// IO registers as the boot ROM leaves them, the same on every model as
+/// far as the registers we keep are concerned. NR52 comes before the other
+/// sound registers, which ignore writes while the APU is off.
+const POST_BOOT_IO: [(u16, u8); 30] = [
+    (0xFF00, 0xCF), (0xFF01, 0x00), (0xFF02, 0x7E), (0xFF05, 0x00),
+    (0xFF06, 0x00), (0xFF07, 0xF8), (0xFF0F, 0xE1), (0xFF26, 0xF1),
+    (0xFF10, 0x80), (0xFF11, 0xBF), (0xFF12, 0xF3), (0xFF13, 0xFF),
+    (0xFF14, 0xBF), (0xFF16, 0x3F), (0xFF17, 0x00), (0xFF18, 0xFF),
+    (0xFF19, 0xBF), (0xFF1A, 0x7F), (0xFF1B, 0xFF), (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF), (0xFF1E, 0xBF), (0xFF20, 0xFF), (0xFF21, 0x00),
+    (0xFF22, 0x00), (0xFF23, 0xBF), (0xFF24, 0x77), (0xFF25, 0xF3),
+    (0xFF40, 0x91), (0xFF47, 0xFC),
+];
 
 pub struct Interconnect {
     bootrom: Box<[u8]>,
+    bootrom_mapped: bool,
     cartridge: Cartridge,
     ppu: PPU,
+    timer: Timer,
+    apu: Apu,
+    interrupts: InterruptController,
+    cgb: bool,
+    double_speed: bool,
+    speed_switch_armed: bool,
     io: Box<[u8]>,
     wram: Box<[u8]>,
     hram: Box<[u8]>,
 }
 
 impl Interconnect {
-    pub fn new(bootrom: Box<[u8]>, rom: Box<[u8]>) -> Self {
-        Self {
+    pub fn new(bootrom: Box<[u8]>, rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        let model = Model::from_bootrom(&bootrom);
+        Self::with_model(model, bootrom, rom)
+    }
+
+    /// Starts out the way the boot ROM of `model` would have left things
+    /// on handing over to the cartridge at $0100
+    pub fn without_bootrom(model: Model, rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        let mut interconnect = Self::with_model(model, Box::new([]), rom)?;
+        interconnect.bootrom_mapped = false;
+
+        for &(addr, val) in POST_BOOT_IO.iter() {
+            interconnect.write_io(addr, val);
+        }
+
+        // the color boot ROMs run for a time that depends on the header,
+        // so only the monochrome DIV is predictable
+        if !model.is_cgb() {
+            interconnect.timer = Timer::with_counter(0xABCC);
+        }
+
+        Ok(interconnect)
+    }
+
+    fn with_model(model: Model, bootrom: Box<[u8]>, rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        Ok(Self {
             bootrom,
-            cartridge: Cartridge::new(rom),
+            bootrom_mapped: true,
+            cartridge: Cartridge::new(rom)?,
             ppu: PPU::new(),
-            io: vec![0xFF; 127].into_boxed_slice(),
+            timer: Timer::new(),
+            apu: Apu::new(),
+            interrupts: InterruptController::new(),
+            cgb: model.is_cgb(),
+            double_speed: false,
+            speed_switch_armed: false,
+            io: vec![0xFF; 0x80].into_boxed_slice(),
             wram: vec![0xFF; 8192].into_boxed_slice(),
             hram: vec![0xFF; 127].into_boxed_slice(),
-        }
+        })
     }
 
-    pub fn read_internal(&self, addr: u16) -> u8 {
-        match addr {
-            0x0...0xFF => self.bootrom[addr as usize],
-            0x0100...0x7FFF => self.cartridge.read(addr),
-            0x8000...0x9FFF => self.ppu.read(addr - 0x8000),
-            0xC000...0xDFFF => self.wram[(addr - 0xC000) as usize],
-            0xFF00...0xFF7F => match addr {
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    /// Advances every device by the T-cycles the CPU just spent
+    pub fn step(&mut self, cycles: usize) {
+        // in double speed mode the PPU, APU and cartridge keep running at the normal rate
+        let dots = if self.double_speed { cycles / 2 } else { cycles };
+
+        let irq = self.timer.step(cycles) | self.ppu.step(dots);
+        self.interrupts.request(irq);
+        self.apu.step(dots);
+        self.cartridge.step(dots);
+    }
+
+    pub fn read_internal(&self, addr: u16) -> Result<u8> {
+        let val = match addr {
+            0x0000..=0x00FF if self.bootrom_mapped => self.bootrom[addr as usize],
+            // the CGB boot ROM leaves a hole for the cartridge header
+            0x0200..=0x08FF if self.bootrom_mapped && (addr as usize) < self.bootrom.len() => {
+                self.bootrom[addr as usize]
+            }
+            0x0000..=0x7FFF => self.cartridge.read(addr)?,
+            0x8000..=0x9FFF => self.ppu.read_vram(addr - 0x8000),
+            0xA000..=0xBFFF => self.cartridge.read_ram(addr),
+            0xC000..=0xDFFF => self.wram[(addr - 0xC000) as usize],
+            // echo RAM mirrors the first 7.5 KiB of WRAM
+            0xE000..=0xFDFF => self.wram[(addr - 0xE000) as usize],
+            0xFE00..=0xFE9F => self.ppu.read_oam(addr - 0xFE00),
+            0xFEA0..=0xFEFF => self.read_unusable(addr),
+            0xFF00..=0xFF7F => match addr {
+                IF_ADDR => self.interrupts.get_flag(),
+                DIV_ADDR..=TAC_ADDR => self.timer.read(addr),
+                NR10_ADDR..=WAVE_END => self.apu.read(addr),
+                KEY1_ADDR if self.cgb => {
+                    let speed = if self.double_speed { 0x80 } else { 0x00 };
+                    let armed = if self.speed_switch_armed { 0x01 } else { 0x00 };
+                    0x7E | speed | armed
+                }
                 0xFF40 => self.ppu.get_control(),
                 0xFF44 => self.ppu.get_current_line(),
                 _ => self.io[(addr - 0xFF00) as usize],
             },
-            0xFF80...0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize],
+            IE_ADDR => self.interrupts.get_enable(),
+        };
 
-            _ => panic!("Unrecognized read address ${:#X}", addr),
+        Ok(val)
+    }
+
+    /// $FEA0-$FEFF isn't backed by anything. It reads $FF while OAM is
+    /// blocked; otherwise DMG returns $00 and CGB repeats the high nibble
+    /// of the address' low byte.
+    fn read_unusable(&self, addr: u16) -> u8 {
+        if self.ppu.oam_blocked() {
+            0xFF
+        } else if self.cgb {
+            let nibble = (addr as u8) >> 4;
+            nibble << 4 | nibble
+        } else {
+            0x00
         }
     }
 
-    fn write_internal(&mut self, addr: u16, val: u8) {
+    fn write_internal(&mut self, addr: u16, val: u8) -> Result<()> {
         match addr {
-            0x0100...0x7FFF => panic!("-- writing cartridge mem val:{:#04X} addr: ${:#06X}", val, addr - 0x0100),
-            0x8000...0x9FFF => self.ppu.write(addr - 0x8000, val),
-            0xC000...0xDFFF => {
+            0x0000..=0x7FFF => self.cartridge.write(addr, val),
+            0x8000..=0x9FFF => self.ppu.write_vram(addr - 0x8000, val),
+            0xA000..=0xBFFF => self.cartridge.write_ram(addr, val),
+            0xC000..=0xDFFF => {
                 self.wram[(addr - 0xC000) as usize] = val;
             }
-            0xFF00...0xFF7F => {
-                match addr {
-                    0xFF40 => self.ppu.set_control(val),
-                    0xFF42 => self.ppu.set_scroll_y(val),
-                    0xFF44 => panic!("kurwa!"),
-                    _ => self.io[(addr - 0xFF00) as usize] = val
-                }
-            }
-            0xFF80...0xFFFE => self.hram[(addr - 0xFF80) as usize] = val,
-            _ => {
-                panic!("!!TODO: write_internal(${:#04X}, {:#02X}): not implemented yet: missing relative adddr to physical addr mapping", addr, val)
+            0xE000..=0xFDFF => self.wram[(addr - 0xE000) as usize] = val,
+            0xFE00..=0xFE9F => self.ppu.write_oam(addr - 0xFE00, val),
+            // writes to the unusable region go nowhere
+            0xFEA0..=0xFEFF => {}
+            0xFF00..=0xFF7F => self.write_io(addr, val),
+            0xFF80..=0xFFFE => self.hram[(addr - 0xFF80) as usize] = val,
+            IE_ADDR => self.interrupts.set_enable(val),
+        }
+
+        Ok(())
+    }
+
+    /// Writes one of the registers at $FF00-$FF7F
+    fn write_io(&mut self, addr: u16, val: u8) {
+        match addr {
+            IF_ADDR => self.interrupts.set_flag(val),
+            DIV_ADDR..=TAC_ADDR => {
+                let irq = self.timer.write(addr, val);
+                self.interrupts.request(irq);
             }
+            NR10_ADDR..=WAVE_END => self.apu.write(addr, val),
+            KEY1_ADDR if self.cgb => self.speed_switch_armed = val & 0x01 != 0,
+            BOOT_ADDR if val != 0 => self.bootrom_mapped = false,
+            0xFF40 => self.ppu.set_control(val),
+            0xFF42 => self.ppu.set_scroll_y(val),
+            // LY is read only
+            0xFF44 => {}
+            _ => self.io[(addr - 0xFF00) as usize] = val
         }
     }
 }
 
 impl MemoryBus for Interconnect {
-    fn read(&self, addr: u16) -> Result<u8, Error> {
-        Ok(self.read_internal(addr))
+    fn read(&self, addr: u16) -> Result<u8> {
+        self.read_internal(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) -> Result<()> {
+        self.write_internal(addr, val)
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.step(cycles)
+    }
+
+    fn speed_switch(&mut self) -> bool {
+        if !self.speed_switch_armed {
+            return false;
+        }
+
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_switch_needs_cgb_and_armed_key1() {
+        let mut dmg = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        dmg.write_internal(KEY1_ADDR, 0x01).unwrap();
+        assert!(!dmg.speed_switch());
+
+        let mut cgb = Interconnect::new(vec![0; 0x900].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        assert!(!cgb.speed_switch());
+
+        cgb.write_internal(KEY1_ADDR, 0x01).unwrap();
+        assert_eq!(cgb.read_internal(KEY1_ADDR), Ok(0x7F));
+        assert!(cgb.speed_switch());
+        assert_eq!(cgb.read_internal(KEY1_ADDR), Ok(0xFE));
     }
 
-    fn write(&mut self, addr: u16, val: u8) -> Result<(), Error> {
-        Ok(self.write_internal(addr, val))
+    #[test]
+    fn boot_rom_unmaps_for_good() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0000] = 0xC3;
+        rom[0x0100] = 0x00;
+        rom[0x0200] = 0x12;
+        let mut ic = Interconnect::new(vec![0x31; 0x900].into_boxed_slice(), rom.into_boxed_slice()).unwrap();
+
+        assert_eq!(ic.read_internal(0x0000), Ok(0x31));
+        assert_eq!(ic.read_internal(0x0100), Ok(0x00));
+        assert_eq!(ic.read_internal(0x0200), Ok(0x31));
+
+        ic.write_internal(BOOT_ADDR, 0x00).unwrap();
+        assert_eq!(ic.read_internal(0x0000), Ok(0x31));
+
+        ic.write_internal(BOOT_ADDR, 0x11).unwrap();
+        assert_eq!(ic.read_internal(0x0000), Ok(0xC3));
+        assert_eq!(ic.read_internal(0x0200), Ok(0x12));
+
+        ic.write_internal(BOOT_ADDR, 0x00).unwrap();
+        assert_eq!(ic.read_internal(0x0000), Ok(0xC3));
+    }
+
+    #[test]
+    fn skipping_the_boot_rom_sets_up_io() {
+        let dmg = Interconnect::without_bootrom(Model::Dmg, vec![0; 0x8000].into_boxed_slice()).unwrap();
+        assert_eq!(dmg.read_internal(0x0000), Ok(0x00));
+        assert_eq!(dmg.read_internal(DIV_ADDR), Ok(0xAB));
+        assert_eq!(dmg.read_internal(IF_ADDR), Ok(0xE1));
+        assert_eq!(dmg.read_internal(0xFF40), Ok(0x91));
+        assert_eq!(dmg.read_internal(KEY1_ADDR), Ok(0xFF));
+        // only channel 1 is left playing, the boot chime
+        assert_eq!(dmg.read_internal(0xFF26), Ok(0xF1));
+        assert_eq!(dmg.read_internal(0xFF24), Ok(0x77));
+
+        let cgb = Interconnect::without_bootrom(Model::Cgb, vec![0; 0x8000].into_boxed_slice()).unwrap();
+        assert_eq!(cgb.read_internal(KEY1_ADDR), Ok(0x7E));
+    }
+
+    #[test]
+    fn ignored_writes_are_not_faults() {
+        let mut ic = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+
+        // writes the hardware ignores are not faults
+        assert_eq!(ic.write_internal(0x2000, 0x01), Ok(()));
+        assert_eq!(ic.write_internal(0xFF44, 0x00), Ok(()));
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut ic = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+
+        ic.write_internal(0xC123, 0x42).unwrap();
+        assert_eq!(ic.read_internal(0xE123), Ok(0x42));
+
+        ic.write_internal(0xFDFF, 0x24).unwrap();
+        assert_eq!(ic.read_internal(0xDDFF), Ok(0x24));
+    }
+
+    #[test]
+    fn unusable_region_reads_per_model() {
+        let mut dmg = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        dmg.write_internal(0xFEA0, 0x12).unwrap();
+        assert_eq!(dmg.read_internal(0xFEA0), Ok(0x00));
+
+        let cgb = Interconnect::new(vec![0; 0x900].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        assert_eq!(cgb.read_internal(0xFEB7), Ok(0xBB));
+
+        // with OAM in use it floats high on every model
+        dmg.write_internal(0xFF40, 0x80).unwrap();
+        dmg.step(4);
+        assert_eq!(dmg.read_internal(0xFEA0), Ok(0xFF));
+        assert_eq!(dmg.read_internal(0xFE00), Ok(0xFF));
+    }
+
+    #[test]
+    fn apu_runs_with_the_cpu_clock() {
+        let mut ic = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        ic.write_internal(0xFF26, 0x80).unwrap();
+        // channel 2 with its DAC on and a single tick of length left
+        ic.write_internal(0xFF17, 0xF0).unwrap();
+        ic.write_internal(0xFF16, 0x3F).unwrap();
+        ic.write_internal(0xFF19, 0xC0).unwrap();
+        assert_eq!(ic.read_internal(0xFF26), Ok(0xF2));
+
+        ic.step(8192);
+        assert_eq!(ic.read_internal(0xFF26), Ok(0xF0));
+    }
+
+    #[test]
+    fn external_ram_without_a_chip_floats_high() {
+        let mut ic = Interconnect::new(vec![0; 0x100].into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+        ic.write_internal(0xA000, 0x12).unwrap();
+        assert_eq!(ic.read_internal(0xA000), Ok(0xFF));
+
+        let mut rom = vec![0; 0x8000];
+        rom[0x0149] = 0x02;
+        let mut ic = Interconnect::new(vec![0; 0x100].into_boxed_slice(), rom.into_boxed_slice()).unwrap();
+        ic.write_internal(0xBFFF, 0x12).unwrap();
+        assert_eq!(ic.read_internal(0xBFFF), Ok(0x12));
     }
 }
@@ -0,0 +1,101 @@
+pub const IF_ADDR: u16 = 0xFF0F;
+pub const IE_ADDR: u16 = 0xFFFF;
+
+bitflags!(
+  pub struct Interrupt: u8 {
+    const VBLANK   = 0b_0000_0001;
+    const LCD_STAT = 0b_0000_0010;
+    const TIMER    = 0b_0000_0100;
+    const SERIAL   = 0b_0000_1000;
+    const JOYPAD   = 0b_0001_0000;
+  }
+);
+
+impl Interrupt {
+    /// The pending interrupt that gets serviced first: the lowest bit wins
+    pub fn highest_priority(&self) -> Option<Interrupt> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(Interrupt::from_bits_truncate(self.bits() & self.bits().wrapping_neg()))
+        }
+    }
+
+    /// Address of the service routine for a single interrupt
+    pub fn vector(&self) -> u16 {
+        0x0040 + 8 * self.bits().trailing_zeros() as u16
+    }
+}
+
+/// The IE ($FFFF) and IF ($FF0F) registers. Devices raise requests through
+/// `request`, the CPU picks them up via the memory bus.
+pub struct InterruptController {
+    enable: u8,
+    flag: Interrupt,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self {
+            enable: 0x00,
+            flag: Interrupt::empty(),
+        }
+    }
+
+    pub fn request(&mut self, irq: Interrupt) {
+        self.flag |= irq;
+    }
+
+    pub fn get_enable(&self) -> u8 {
+        self.enable
+    }
+
+    pub fn set_enable(&mut self, val: u8) {
+        self.enable = val
+    }
+
+    pub fn get_flag(&self) -> u8 {
+        // the three unused bits of IF always read back as set
+        0xE0 | self.flag.bits()
+    }
+
+    pub fn set_flag(&mut self, val: u8) {
+        self.flag = Interrupt::from_bits_truncate(val)
+    }
+}
+
+impl Default for InterruptController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority_and_vectors() {
+        assert_eq!(Interrupt::empty().highest_priority(), None);
+        assert_eq!((Interrupt::JOYPAD | Interrupt::TIMER).highest_priority(), Some(Interrupt::TIMER));
+        assert_eq!(Interrupt::all().highest_priority(), Some(Interrupt::VBLANK));
+
+        assert_eq!(Interrupt::VBLANK.vector(), 0x40);
+        assert_eq!(Interrupt::LCD_STAT.vector(), 0x48);
+        assert_eq!(Interrupt::TIMER.vector(), 0x50);
+        assert_eq!(Interrupt::SERIAL.vector(), 0x58);
+        assert_eq!(Interrupt::JOYPAD.vector(), 0x60);
+    }
+
+    #[test]
+    fn unused_flag_bits_read_as_set() {
+        let mut ic = InterruptController::new();
+
+        ic.set_flag(0xFF);
+        assert_eq!(ic.get_flag(), 0xFF);
+
+        ic.set_flag(0x00);
+        ic.request(Interrupt::SERIAL);
+        assert_eq!(ic.get_flag(), 0xE8);
+    }
+}
@@ -1,6 +1,11 @@
-mod bus;
+mod apu;
+pub mod bus;
 
 pub mod cpu;
+pub mod error;
 pub mod interconnect;
+pub mod interrupts;
+pub mod model;
 mod ppu;
-mod cartridge;
+pub mod cartridge;
+mod timer;
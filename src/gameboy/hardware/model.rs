@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+/// The hardware revisions a game can run on. Without a boot ROM they only
+/// differ in the state the boot ROM would have left behind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// The original Game Boy
+    Dmg,
+    /// Game Boy Pocket and Light
+    Mgb,
+    /// Game Boy Color
+    Cgb,
+    /// Game Boy Advance, running Game Boy software
+    Agb,
+}
+
+impl Model {
+    /// Guesses the model from the size of its boot ROM: only the color
+    /// models have more than 256 bytes
+    pub fn from_bootrom(bootrom: &[u8]) -> Self {
+        if bootrom.len() > 0x100 {
+            Model::Cgb
+        } else {
+            Model::Dmg
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        *self == Model::Cgb || *self == Model::Agb
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "cgb" => Ok(Model::Cgb),
+            "agb" => Ok(Model::Agb),
+            _ => Err(format!("unknown model {}", s)),
+        }
+    }
+}
@@ -1,17 +1,30 @@
 use std::fmt;
 
+use super::interrupts::Interrupt;
+
 use self::registers::Control;
 
 pub mod registers;
 
-#[derive(PartialEq, Eq)]
-enum Mode {
+const DOTS_PER_LINE: usize = 456;
+const VISIBLE_LINES: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+const OAM_SIZE: usize = 0xA0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    HBlank,
     VBlank,
+    OamSearch,
+    Transfer,
 }
 
 pub struct PPU {
     vram: Box<[u8]>,
+    oam: Box<[u8]>,
     control: Control,
+    mode: Mode,
+    dots: usize,
     current_line: u8,
     scroll_x: u8,
     scroll_y: u8,
@@ -21,24 +34,73 @@ impl PPU {
     pub fn new() -> Self {
         Self {
             vram: vec![0xFF; 8192].into_boxed_slice(),
+            oam: vec![0xFF; OAM_SIZE].into_boxed_slice(),
             control: Control::empty(),
+            mode: Mode::HBlank,
 
+            dots: 0,
             current_line: 0,
             scroll_x: 0,
             scroll_y: 0,
         }
     }
 
+    /// Advances the PPU by the given number of dots and returns the
+    /// interrupts raised on the way
+    pub fn step(&mut self, cycles: usize) -> Interrupt {
+        let mut irq = Interrupt::empty();
+
+        if !self.control.contains(Control::LCD_DISPLAY_ENABLE) {
+            return irq;
+        }
+
+        self.dots += cycles;
+
+        while self.dots >= DOTS_PER_LINE {
+            self.dots -= DOTS_PER_LINE;
+            self.current_line = (self.current_line + 1) % LINES_PER_FRAME;
+
+            if self.current_line == VISIBLE_LINES {
+                irq |= Interrupt::VBLANK;
+            }
+        }
+
+        self.mode = if self.current_line >= VISIBLE_LINES {
+            Mode::VBlank
+        } else if self.dots < 80 {
+            Mode::OamSearch
+        } else if self.dots < 252 {
+            Mode::Transfer
+        } else {
+            Mode::HBlank
+        };
+
+        irq
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
+
     pub fn get_current_line(&self) -> u8 {
         self.current_line
     }
 
     pub fn get_control(&self) -> u8 {
-        unimplemented!("get_control()")
+        self.control.bits()
     }
 
     pub fn set_control(&mut self, val: u8) {
-        unimplemented!("set_control({:08b})", val)
+        let control = Control::from_bits_truncate(val);
+
+        // switching the LCD off resets the frame to the top
+        if !control.contains(Control::LCD_DISPLAY_ENABLE) {
+            self.dots = 0;
+            self.current_line = 0;
+            self.mode = Mode::HBlank;
+        }
+
+        self.control = control
     }
 
     pub fn get_scroll_y(&self) -> u8 {
@@ -49,12 +111,43 @@ impl PPU {
         self.scroll_y = val
     }
 
-    pub fn read(&self, addr: u16) -> u8 {
+    /// The CPU can't reach VRAM while the PPU is fetching from it. Blocked
+    /// reads return $FF and blocked writes are dropped.
+    pub fn vram_blocked(&self) -> bool {
+        self.mode == Mode::Transfer
+    }
+
+    /// OAM is in use from the sprite search up to the end of the transfer
+    pub fn oam_blocked(&self) -> bool {
+        self.mode == Mode::OamSearch || self.mode == Mode::Transfer
+    }
+
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        if self.vram_blocked() {
+            return 0xFF;
+        }
+
         self.vram[addr as usize]
     }
 
-    pub fn write(&mut self, addr: u16, val: u8) {
-        self.vram[addr as usize] = val
+    pub fn write_vram(&mut self, addr: u16, val: u8) {
+        if !self.vram_blocked() {
+            self.vram[addr as usize] = val
+        }
+    }
+
+    pub fn read_oam(&self, addr: u16) -> u8 {
+        if self.oam_blocked() {
+            return 0xFF;
+        }
+
+        self.oam[addr as usize]
+    }
+
+    pub fn write_oam(&mut self, addr: u16, val: u8) {
+        if !self.oam_blocked() {
+            self.oam[addr as usize] = val
+        }
     }
 }
 
@@ -63,3 +156,67 @@ impl fmt::Debug for PPU {
         write!(f, "Control:{:08b}", self.control.bits(), )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_modes_and_vblank() {
+        let mut ppu = PPU::new();
+        ppu.set_control(0x80);
+
+        assert_eq!(ppu.step(80), Interrupt::empty());
+        assert_eq!(ppu.get_mode(), Mode::Transfer);
+        assert_eq!(ppu.step(172), Interrupt::empty());
+        assert_eq!(ppu.get_mode(), Mode::HBlank);
+        assert_eq!(ppu.step(204), Interrupt::empty());
+        assert_eq!(ppu.get_current_line(), 1);
+        assert_eq!(ppu.get_mode(), Mode::OamSearch);
+
+        assert_eq!(ppu.step(143 * DOTS_PER_LINE), Interrupt::VBLANK);
+        assert_eq!(ppu.get_current_line(), 144);
+        assert_eq!(ppu.get_mode(), Mode::VBlank);
+
+        assert_eq!(ppu.step(10 * DOTS_PER_LINE), Interrupt::empty());
+        assert_eq!(ppu.get_current_line(), 0);
+    }
+
+    #[test]
+    fn lcd_off_holds_line_zero() {
+        let mut ppu = PPU::new();
+        assert_eq!(ppu.step(10 * DOTS_PER_LINE), Interrupt::empty());
+        assert_eq!(ppu.get_current_line(), 0);
+
+        ppu.set_control(0x80);
+        ppu.step(3 * DOTS_PER_LINE);
+        assert_eq!(ppu.get_current_line(), 3);
+
+        ppu.set_control(0x00);
+        assert_eq!(ppu.get_current_line(), 0);
+    }
+
+    #[test]
+    fn memory_is_blocked_by_mode() {
+        let mut ppu = PPU::new();
+        ppu.write_vram(0x0000, 0x12);
+        ppu.write_oam(0x0000, 0x34);
+
+        ppu.set_control(0x80);
+        ppu.step(4);
+        assert_eq!(ppu.get_mode(), Mode::OamSearch);
+        assert_eq!(ppu.read_vram(0x0000), 0x12);
+        assert_eq!(ppu.read_oam(0x0000), 0xFF);
+
+        ppu.step(80);
+        assert_eq!(ppu.get_mode(), Mode::Transfer);
+        ppu.write_vram(0x0000, 0x56);
+        assert_eq!(ppu.read_vram(0x0000), 0xFF);
+        assert_eq!(ppu.read_oam(0x0000), 0xFF);
+
+        ppu.step(172);
+        assert_eq!(ppu.get_mode(), Mode::HBlank);
+        assert_eq!(ppu.read_vram(0x0000), 0x12);
+        assert_eq!(ppu.read_oam(0x0000), 0x34);
+    }
+}
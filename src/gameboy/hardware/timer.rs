@@ -0,0 +1,143 @@
+use super::interrupts::Interrupt;
+
+pub const DIV_ADDR: u16 = 0xFF04;
+pub const TIMA_ADDR: u16 = 0xFF05;
+pub const TMA_ADDR: u16 = 0xFF06;
+pub const TAC_ADDR: u16 = 0xFF07;
+
+/// DIV, TIMA, TMA and TAC. DIV is the upper byte of a 16-bit counter that
+/// ticks every T-cycle; TIMA counts falling edges of one of its bits.
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+        }
+    }
+
+    /// A timer whose internal counter has already been running, as left by
+    /// the boot ROM
+    pub fn with_counter(counter: u16) -> Self {
+        Self {
+            counter,
+            ..Self::new()
+        }
+    }
+
+    /// Advances the timer and returns the interrupts raised on the way
+    pub fn step(&mut self, cycles: usize) -> Interrupt {
+        let mut irq = Interrupt::empty();
+
+        for _ in 0..cycles {
+            let before = self.input();
+            self.counter = self.counter.wrapping_add(1);
+
+            if before && !self.input() {
+                irq |= self.increment();
+            }
+        }
+
+        irq
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            DIV_ADDR => (self.counter >> 8) as u8,
+            TIMA_ADDR => self.tima,
+            TMA_ADDR => self.tma,
+            TAC_ADDR => 0xF8 | self.tac,
+            _ => unreachable!("timer read ${:04X}", addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8) -> Interrupt {
+        // both writes can pull the selected counter bit low, which counts as an edge
+        let before = self.input();
+
+        match addr {
+            DIV_ADDR => self.counter = 0,
+            TIMA_ADDR => self.tima = val,
+            TMA_ADDR => self.tma = val,
+            TAC_ADDR => self.tac = val & 0x07,
+            _ => unreachable!("timer write ${:04X}", addr),
+        }
+
+        if before && !self.input() {
+            self.increment()
+        } else {
+            Interrupt::empty()
+        }
+    }
+
+    fn input(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+
+        self.tac & 0x04 != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn increment(&mut self) -> Interrupt {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+
+        if overflow {
+            self.tima = self.tma;
+            Interrupt::TIMER
+        } else {
+            self.tima = tima;
+            Interrupt::empty()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_counts_every_256_cycles() {
+        let mut timer = Timer::new();
+        timer.step(255);
+        assert_eq!(timer.read(DIV_ADDR), 0);
+        timer.step(1);
+        assert_eq!(timer.read(DIV_ADDR), 1);
+
+        timer.write(DIV_ADDR, 0xAB);
+        assert_eq!(timer.read(DIV_ADDR), 0);
+    }
+
+    #[test]
+    fn tima_overflow_reloads_and_interrupts() {
+        let mut timer = Timer::new();
+        timer.write(TMA_ADDR, 0xF0);
+        timer.write(TIMA_ADDR, 0xFE);
+        // enabled, one increment every 16 cycles
+        timer.write(TAC_ADDR, 0x05);
+        assert_eq!(timer.read(TAC_ADDR), 0xFD);
+
+        assert_eq!(timer.step(16), Interrupt::empty());
+        assert_eq!(timer.read(TIMA_ADDR), 0xFF);
+        assert_eq!(timer.step(16), Interrupt::TIMER);
+        assert_eq!(timer.read(TIMA_ADDR), 0xF0);
+    }
+
+    #[test]
+    fn disabled_timer_does_not_count() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0x01);
+        timer.step(1024);
+        assert_eq!(timer.read(TIMA_ADDR), 0);
+    }
+}
@@ -1,32 +1,205 @@
+pub use self::hardware::cartridge::{Frames, RtcClock};
+pub use self::hardware::cpu::Timing;
+pub use self::hardware::error::EmuError;
+pub use self::hardware::model::Model;
+
+use std::io;
+use std::path::PathBuf;
+
+use self::battery::Battery;
+use self::hardware::cartridge::{Cartridge, CartridgeHeader, CgbSupport, HeaderError};
+use self::hardware::cpu::disassembler;
 use self::hardware::cpu::LR35902;
 use self::hardware::cpu::registers::Reg16;
+use self::hardware::error::Result;
 use self::hardware::interconnect::Interconnect;
 
-mod hardware;
+mod battery;
+pub mod hardware;
 
 pub struct GameBoy {
     pub cpu: LR35902,
     interconnect: Interconnect,
+    cycles: u64,
+    battery: Option<Battery>,
 }
 
 impl GameBoy {
-    pub fn new(bootrom: Box<[u8]>, rom: Box<[u8]>) -> Self {
-        Self {
+    pub fn new(bootrom: Box<[u8]>, rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        Ok(Self {
             cpu: LR35902::new(),
-            interconnect: Interconnect::new(bootrom, rom),
+            interconnect: Interconnect::new(bootrom, rom)?,
+            cycles: 0,
+            battery: None,
+        })
+    }
+
+    /// Starts executing the cartridge at $0100 right away, with the CPU
+    /// and IO registers set up the way the boot ROM of `model` leaves them
+    pub fn without_bootrom(model: Model, rom: Box<[u8]>) -> ::std::result::Result<Self, HeaderError> {
+        let interconnect = Interconnect::without_bootrom(model, rom)?;
+        let mut cpu = LR35902::new();
+        let (af, bc, de, hl) = post_boot_registers(model, interconnect.cartridge().header());
+
+        cpu.registers.write16(Reg16::AF, af);
+        cpu.registers.write16(Reg16::BC, bc);
+        cpu.registers.write16(Reg16::DE, de);
+        cpu.registers.write16(Reg16::HL, hl);
+        cpu.registers.write16(Reg16::SP, 0xFFFE);
+        cpu.registers.write16(Reg16::PC, 0x0100);
+
+        Ok(Self {
+            cpu,
+            interconnect,
+            cycles: 0,
+            battery: None,
+        })
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        self.interconnect.cartridge()
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        self.interconnect.cartridge_mut()
+    }
+
+    /// Keeps battery-backed RAM in the save file at `path`, loading it if it
+    /// exists. It's written a moment after the game saves, and when the
+    /// `GameBoy` is dropped. Cartridges without a battery leave it alone.
+    pub fn use_save_file<P: Into<PathBuf>>(&mut self, path: P) -> io::Result<()> {
+        let cartridge = self.interconnect.cartridge_mut();
+        if cartridge.has_battery() {
+            self.battery = Some(Battery::open(path.into(), cartridge)?);
         }
+
+        Ok(())
     }
-    
+
+    /// Writes the save file right away
+    pub fn flush_save_file(&mut self) -> io::Result<()> {
+        match self.battery {
+            Some(ref mut battery) => battery.flush(self.interconnect.cartridge_mut()),
+            None => Ok(()),
+        }
+    }
+
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.cpu.set_timing(timing)
+    }
+
     pub fn pc(&self) -> u16 {
         self.cpu.registers.read16(Reg16::PC)
     }
 
-    pub fn mem(&self, addr: u16) -> u8 {
+    pub fn mem(&self, addr: u16) -> Result<u8> {
         self.interconnect.read_internal(addr)
     }
 
-    pub fn step(&mut self) {
-        let addr = self.cpu.registers.read16(Reg16::PC);
-        println!("${:04X} {:}", addr, self.cpu.step(&mut self.interconnect));
+    /// The instruction at `addr` in assembler syntax, and its length.
+    /// Doesn't disturb the emulation.
+    pub fn disassemble(&self, addr: u16) -> (String, usize) {
+        disassembler::render(&self.interconnect, addr)
+    }
+
+    /// How the instruction at `addr` affects Z, N, H and C, e.g. `Z0H-`
+    pub fn flag_effects(&self, addr: u16) -> &'static str {
+        disassembler::flags(&self.interconnect, addr)
+    }
+
+    /// Total T-cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Runs one CPU step; the CPU ticks the other devices along as it goes,
+    /// see `Timing`. Returns the T-cycles spent, or the fault that stopped
+    /// the instruction; the machine stays usable either way, and the time
+    /// a faulting step took still counts.
+    pub fn step(&mut self) -> Result<usize> {
+        let result = self.cpu.step(&mut self.interconnect);
+        let cycles = self.cpu.ticked();
+        self.cycles += cycles as u64;
+
+        if let Some(ref mut battery) = self.battery {
+            battery.step(self.interconnect.cartridge_mut(), cycles);
+        }
+
+        result
+    }
+}
+
+impl Drop for GameBoy {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush_save_file() {
+            error!("couldn't write the save file: {}", err);
+        }
+    }
+}
+
+/// AF, BC, DE and HL at the end of the boot ROM. The monochrome models set
+/// H and C from the header checksum, the color ones tell CGB games apart.
+fn post_boot_registers(model: Model, header: &CartridgeHeader) -> (u16, u16, u16, u16) {
+    let flags = if header.header_checksum == 0x00 { 0x80 } else { 0xB0 };
+    let cgb_game = header.cgb != CgbSupport::None;
+
+    match model {
+        Model::Dmg => (0x0100 | flags, 0x0013, 0x00D8, 0x014D),
+        Model::Mgb => (0xFF00 | flags, 0x0013, 0x00D8, 0x014D),
+        Model::Cgb if cgb_game => (0x1180, 0x0000, 0xFF56, 0x000D),
+        Model::Cgb => (0x1180, 0x0000, 0x0008, 0x007C),
+        Model::Agb if cgb_game => (0x1100, 0x0100, 0xFF56, 0x000D),
+        Model::Agb => (0x1100, 0x0100, 0x0008, 0x007C),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_accumulates_cpu_cycles() {
+        // NOP ; LD BC,$0000 ; JP $0000
+        let mut bootrom = vec![0x00; 0x100];
+        bootrom[..7].copy_from_slice(&[0x00, 0x01, 0x00, 0x00, 0xC3, 0x00, 0x00]);
+        let mut gb = GameBoy::new(bootrom.into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+
+        assert_eq!(gb.step(), Ok(4));
+        assert_eq!(gb.step(), Ok(12));
+        assert_eq!(gb.step(), Ok(16));
+        assert_eq!(gb.cycles(), 32);
+        assert_eq!(gb.pc(), 0x0000);
+    }
+
+    #[test]
+    fn faulting_steps_still_take_time() {
+        // NOP ; illegal $D3
+        let mut bootrom = vec![0x00; 0x100];
+        bootrom[..2].copy_from_slice(&[0x00, 0xD3]);
+        let mut gb = GameBoy::new(bootrom.into_boxed_slice(), vec![0; 0x8000].into_boxed_slice()).unwrap();
+
+        assert_eq!(gb.step(), Ok(4));
+        assert!(gb.step().is_err());
+        assert!(gb.step().is_err());
+        assert_eq!(gb.cycles(), 12);
+    }
+
+    #[test]
+    fn no_bootrom_starts_at_the_entry_point() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x014D] = 0x42;
+        let gb = GameBoy::without_bootrom(Model::Dmg, rom.clone().into_boxed_slice()).unwrap();
+
+        assert_eq!(gb.pc(), 0x0100);
+        assert_eq!(gb.cpu.registers.read16(Reg16::AF), 0x01B0);
+        assert_eq!(gb.cpu.registers.read16(Reg16::SP), 0xFFFE);
+        assert_eq!(gb.mem(0x0000), Ok(0x00));
+
+        rom[0x0143] = 0x80;
+        let gb = GameBoy::without_bootrom(Model::Agb, rom.into_boxed_slice()).unwrap();
+        assert_eq!(gb.cpu.registers.read16(Reg16::AF), 0x1100);
+        assert_eq!(gb.cpu.registers.read16(Reg16::BC), 0x0100);
+        assert_eq!(gb.cpu.registers.read16(Reg16::DE), 0xFF56);
     }
 }
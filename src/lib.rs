@@ -0,0 +1,6 @@
+#[macro_use]
+extern crate bitflags;
+#[macro_use]
+extern crate log;
+
+pub mod gameboy;
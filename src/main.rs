@@ -3,43 +3,107 @@
 #![cfg_attr(feature = "dev", plugin(clippy))]
 
 #[macro_use]
-extern crate bitflags;
-extern crate failure;
 extern crate log;
+extern crate oxiboy;
 extern crate simplelog;
 
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fmt::Display;
+use std::fs;
 use std::path::Path;
+use std::process;
+use std::str::FromStr;
 
 use log::LevelFilter;
 use simplelog::{CombinedLogger, Config, TermLogger};
 use debugger::Debugger;
 
 use emulation::Emulator;
-use gameboy::GameBoy;
+use oxiboy::gameboy::{Frames, GameBoy, Model, RtcClock, Timing};
 
 mod debugger;
 
-mod gameboy;
 mod emulation;
 
 // use debugger::Debugger;
 
+const USAGE: &str = "usage: oxiboy [--bootrom <file>] [--model dmg|mgb|cgb|agb] [--m-cycle] [--rtc host|emulated] [--camera <pgm file or dir>] <rom>";
+
 fn main() {
     CombinedLogger::init(vec![
         TermLogger::new(LevelFilter::Debug, Config::default()).unwrap()
     ])
         .unwrap();
 
-    let bootrom_file_name = env::args().nth(1).unwrap();
-    let bootrom = read_bin(bootrom_file_name);
+    let mut bootrom_file_name = None;
+    let mut rom_file_name = None;
+    let mut model = Model::Dmg;
+    let mut timing = Timing::Batch;
+    let mut rtc_clock = RtcClock::Host;
+    let mut camera_path = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bootrom" => bootrom_file_name = args.next(),
+            "--model" => model = parse_arg(args.next()),
+            // slower, but needed by timing sensitive test ROMs
+            "--m-cycle" => timing = Timing::MCycle,
+            // keeps in-game time in step with the emulation, e.g. when debugging
+            "--rtc" => rtc_clock = parse_arg(args.next()),
+            "--camera" => camera_path = args.next(),
+            _ => rom_file_name = Some(arg),
+        }
+    }
+
+    let rom_file_name = match rom_file_name {
+        Some(name) => name,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let rom = read_bin(&rom_file_name);
+
+    // without a boot ROM the emulation starts where it would have handed over
+    let gb = match bootrom_file_name {
+        Some(name) => GameBoy::new(read_bin(name), rom),
+        None => GameBoy::without_bootrom(model, rom),
+    };
+
+    let mut gb = match gb {
+        Ok(gb) => gb,
+        Err(err) => {
+            eprintln!("oxiboy: {}", err);
+            process::exit(1);
+        }
+    };
+    gb.set_timing(timing);
+    gb.cartridge_mut().set_rtc_clock(rtc_clock);
+    gb.cartridge_mut()
+        .set_rumble_callback(|on| info!("rumble {}", if on { "on" } else { "off" }));
 
-    let rom_file_name = env::args().nth(2).unwrap();
-    let rom = read_bin(rom_file_name);
+    if let Some(path) = camera_path {
+        match Frames::open(&path) {
+            Ok(frames) => gb.cartridge_mut().set_camera_frames(frames),
+            Err(err) => {
+                eprintln!("oxiboy: {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
 
-    let gb = GameBoy::new(bootrom, rom);
+    // named like other emulators name them, so saves can be swapped
+    let save_file_name = Path::new(&rom_file_name).with_extension("sav");
+    if let Err(err) = gb.use_save_file(&save_file_name) {
+        eprintln!("oxiboy: {}: {}", save_file_name.display(), err);
+        process::exit(1);
+    }
+
+    println!("{}\n", gb.cartridge().header());
+    if let Err(err) = gb.cartridge().verify() {
+        warn!("{}", err);
+    }
 
     // let mut emu = Emulator::new(gb);
     // emu.run();
@@ -48,10 +112,35 @@ fn main() {
     dbg.run();
 }
 
-fn read_bin<P: AsRef<Path>>(path: P) -> Box<[u8]> {
-    let mut file = File::open(path).unwrap();
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).unwrap();
+/// The value of an option, or the usage message and exit if it's missing
+/// or doesn't parse
+fn parse_arg<T>(arg: Option<String>) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+{
+    match arg.map(|arg| arg.parse::<T>()) {
+        Some(Ok(val)) => val,
+        Some(Err(err)) => {
+            eprintln!("oxiboy: {}\n{}", err, USAGE);
+            process::exit(1);
+        }
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
 
-    buffer.into_boxed_slice()
+/// The contents of a ROM file, or an error message and exit if it can't
+/// be read
+fn read_bin<P: AsRef<Path>>(path: P) -> Box<[u8]> {
+    let path = path.as_ref();
+    match fs::read(path) {
+        Ok(buffer) => buffer.into_boxed_slice(),
+        Err(err) => {
+            eprintln!("oxiboy: {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
 }